@@ -0,0 +1,215 @@
+use crate::config::Config;
+use crate::find_last_job_ok::is_job_ok;
+use crate::git::{get_tree_of_paths, FingerprintHash};
+use crate::jobs::{get_pipeline_ids_of_commit, get_pipeline_jobs, GitlabJob};
+use crate::verbose;
+use anyhow::Context;
+use git2::{Oid, Repository, Sort};
+use std::path::Path;
+
+// SKIP_CI_SEARCH
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Search {
+    // the last successful jobs of the project, page by page
+    #[default]
+    Jobs,
+    // the ancestors of HEAD with the same fingerprint, then the jobs of their pipelines
+    Ancestors,
+}
+
+// the ancestors of HEAD (HEAD included) with the fingerprint of HEAD, the most recent first,
+// among the SKIP_CI_ANCESTOR_TO_CHECK_MAX first commits of the history
+pub fn get_ancestors_with_fingerprint(
+    repo: &Repository,
+    skip_files_paths: &Vec<&Path>,
+    fingerprint_hash: FingerprintHash,
+    fingerprint_of_head: &str,
+    ancestor_to_check_max: u32,
+) -> anyhow::Result<Vec<Oid>> {
+    let mut revwalk = repo.revwalk().context("revwalk error")?;
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .context("revwalk error")?;
+    revwalk.push_head().context("Head retrieving error")?;
+    let mut ancestors = Vec::new();
+    for oid in revwalk.take(ancestor_to_check_max as usize) {
+        let oid = oid.context("revwalk error")?;
+        let fingerprint = get_tree_of_paths(repo, &oid.to_string(), skip_files_paths)
+            .and_then(|tree| tree.get_fingerprint(fingerprint_hash));
+        match fingerprint {
+            Ok(fingerprint) if fingerprint == fingerprint_of_head => ancestors.push(oid),
+            Ok(_) => {}
+            Err(e) => verbose!("the commit {oid} doesn't match : {e:#}"),
+        }
+    }
+    verbose!("{} ancestors with the same fingerprint", ancestors.len());
+    Ok(ancestors)
+}
+
+// the successful current job of the pipelines of the ancestors with the fingerprint of HEAD
+pub async fn find_last_job_ok_in_ancestors(
+    config: &Config,
+    repo: &Repository,
+    skip_files_paths: &Vec<&Path>,
+    fingerprint_of_head: &str,
+) -> anyhow::Result<Option<GitlabJob>> {
+    scan_ancestor_jobs(config, repo, skip_files_paths, fingerprint_of_head, |job| {
+        is_job_ok(config, job)
+    })
+    .await
+}
+
+// the jobs of the pipelines of the ancestors with the fingerprint of HEAD, the most recent first,
+// until "is_match" : at most SKIP_CI_ANCESTOR_QUERY_MAX ancestors are queried
+pub async fn scan_ancestor_jobs(
+    config: &Config,
+    repo: &Repository,
+    skip_files_paths: &Vec<&Path>,
+    fingerprint_of_head: &str,
+    mut is_match: impl FnMut(&GitlabJob) -> bool,
+) -> anyhow::Result<Option<GitlabJob>> {
+    let ancestors = get_ancestors_with_fingerprint(
+        repo,
+        skip_files_paths,
+        config.fingerprint_hash,
+        fingerprint_of_head,
+        config.ancestor_to_check_max,
+    )?;
+    for (commit_checked, ancestor) in ancestors.iter().enumerate() {
+        if commit_checked as u32 >= config.ancestor_query_max {
+            verbose!(
+                "ancestor_query_max: {commit_checked} >= {}",
+                config.ancestor_query_max
+            );
+            return Ok(None);
+        }
+        verbose!("Check commit {ancestor}");
+        // the most recent pipeline first
+        for pipeline_id in get_pipeline_ids_of_commit(config, &ancestor.to_string()).await? {
+            let jobs = get_pipeline_jobs(config, pipeline_id).await?;
+            if let Some(job) = jobs.into_iter().find(|job| is_match(job)) {
+                verbose!("job found in the pipeline {pipeline_id} !");
+                return Ok(Some(job));
+            }
+        }
+    }
+    verbose!("job not found ! {} ancestors checked", ancestors.len());
+    Ok(None)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::ancestors::{find_last_job_ok_in_ancestors, get_ancestors_with_fingerprint, Search};
+    use crate::find_last_job_ok::{find_last_job_ok, get_paths_to_check};
+    use crate::git::{get_tree_of_paths, FingerprintHash};
+    use crate::process::tests::{create_config_ok, prepare_tmp_repo};
+    use git2::Oid;
+    use httptest::matchers::*;
+    use httptest::responders::status_code;
+    use httptest::{all_of, Expectation, Server};
+    use std::path::Path;
+
+    pub const COMMIT_01: &str = "ef08d93fdeabf23734248d6f95ab4ff3952e9856";
+    pub const COMMIT_02: &str = "260d47a1192add224652749a67fa0ac71370b83c";
+    pub const COMMIT_03: &str = "71caf060ef3022468ffd8b4a70e680d7fec78000";
+    pub const COMMIT_04: &str = "5e694dadd2979a2680c98c88a2f98df9787947d2";
+
+    pub fn add_pipelines_expect(server: &Server, sha: &str, body: &str) {
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/pipelines"),
+                request::query(url_decoded(contains(("sha", sha.to_string())))),
+            ])
+            .times(1)
+            .respond_with(status_code(200).body(body.to_string())),
+        );
+    }
+
+    pub fn add_pipeline_jobs_expect(server: &Server) {
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/pipelines/7/jobs"),
+                request::query(url_decoded(contains(("scope", "success")))),
+            ])
+            .times(1)
+            .respond_with(status_code(200).body(format!(
+                r#"[
+  {{"artifacts_expire_at": null, "commit": {{"id": "{COMMIT_03}"}}, "id": 12345677,
+    "name": "jobB", "ref": "branch1", "status": "success", "web_url": ""}},
+  {{"artifacts_expire_at": null, "commit": {{"id": "{COMMIT_03}"}}, "id": 12345678,
+    "name": "jobA", "ref": "branch1", "status": "success", "web_url": ""}}
+]"#
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_get_ancestors_with_fingerprint() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        repo.set_head_detached(Oid::from_str(COMMIT_04).unwrap())
+            .unwrap();
+        let config = create_config_ok(&tmp_dir, "");
+        let paths_to_check = get_paths_to_check(&config).unwrap();
+        let skip_files_paths = paths_to_check.iter().map(Path::new).collect();
+        let fingerprint = get_tree_of_paths(&repo, COMMIT_04, &skip_files_paths)
+            .unwrap()
+            .get_fingerprint(FingerprintHash::Sha1)
+            .unwrap();
+        let ancestors = |fingerprint: &str, max: u32| {
+            get_ancestors_with_fingerprint(
+                &repo,
+                &skip_files_paths,
+                FingerprintHash::Sha1,
+                fingerprint,
+                max,
+            )
+            .unwrap()
+            .iter()
+            .map(Oid::to_string)
+            .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            ancestors(&fingerprint, 100),
+            vec![COMMIT_04, COMMIT_03, COMMIT_02, COMMIT_01]
+        );
+        // only the HEAD is checked
+        assert_eq!(ancestors(&fingerprint, 1), vec![COMMIT_04]);
+        assert!(ancestors("0000000000000000000000000000000000000000", 100).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_last_job_ok_in_ancestors() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        repo.set_head_detached(Oid::from_str(COMMIT_04).unwrap())
+            .unwrap();
+        let server = Server::run();
+        add_pipelines_expect(&server, COMMIT_04, "[]");
+        add_pipelines_expect(&server, COMMIT_03, r#"[{"id": 7, "status": "failed"}]"#);
+        add_pipeline_jobs_expect(&server);
+        let mut config = create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"));
+        config.search = Search::Ancestors;
+        let res = find_last_job_ok(&config).await;
+        assert_eq!(res.unwrap().unwrap().id, 12345678);
+    }
+
+    #[tokio::test]
+    async fn test_find_last_job_ok_in_ancestors_max() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        repo.set_head_detached(Oid::from_str(COMMIT_04).unwrap())
+            .unwrap();
+        let server = Server::run();
+        add_pipelines_expect(&server, COMMIT_04, "[]");
+        let mut config = create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"));
+        // the commit 03 is not queried
+        config.ancestor_query_max = 1;
+        let paths_to_check = get_paths_to_check(&config).unwrap();
+        let skip_files_paths = paths_to_check.iter().map(Path::new).collect();
+        let fingerprint = get_tree_of_paths(&repo, COMMIT_04, &skip_files_paths)
+            .unwrap()
+            .get_fingerprint(FingerprintHash::Sha1)
+            .unwrap();
+        let res =
+            find_last_job_ok_in_ancestors(&config, &repo, &skip_files_paths, &fingerprint).await;
+        assert_eq!(res.unwrap(), None);
+    }
+}
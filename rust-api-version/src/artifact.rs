@@ -1,9 +1,11 @@
+use crate::auth::Credential;
 use crate::config::Config;
+use crate::http_client::{next_chunk, send, HttpSettings};
 use crate::jobs::GitlabJob;
 use crate::skipci_log::yellow;
 use crate::verbose;
 use anyhow::{anyhow, Context};
-use hyper::body::HttpBody;
+use hyper::{Body, Request};
 use std::fs::File;
 use std::io::Write;
 use tempfile::tempdir;
@@ -12,23 +14,7 @@ pub async fn extract_artifacts(config: &Config, job: &GitlabJob) -> anyhow::Resu
     match job.artifacts_expire_at.clone() {
         Some(artifacts_expire_at) => {
             verbose!("Artifact expire_at : {artifacts_expire_at}");
-            let tmp_dir = tempdir().context("Create temp dir error")?;
-            let tmp_file = tmp_dir.path().join("artifact.zip");
-            let tmp_file_path = tmp_file.to_str().context("Error path to str")?;
-            let token = &config
-                .ci_job_token
-                .clone()
-                .context("CI_JOB_TOKEN undefined")?;
-            let artifact_url = format!("{}/{}/artifacts", &config.jobs_api_url, &job.id);
-            verbose!("download artifact {artifact_url} to {tmp_file_path}");
-            let artifact_url = format!("{artifact_url}?job_token={token}");
-            let download_ok = download_file(&artifact_url, tmp_file_path).await?;
-            if download_ok {
-                extract_archive(tmp_file_path, &config.project_path)?;
-                verbose!("extract_artifacts is OK");
-            } else {
-                yellow("artifact not found");
-            }
+            extract_artifacts_of_job_id(config, u64::from(job.id)).await?;
             Ok(true)
         }
         None => {
@@ -38,32 +24,63 @@ pub async fn extract_artifacts(config: &Config, job: &GitlabJob) -> anyhow::Resu
     }
 }
 
-async fn download_file(url: &str, file_path: &str) -> anyhow::Result<bool> {
+// download and extract the artifacts of the job, return false if the artifacts are not found
+pub async fn extract_artifacts_of_job_id(config: &Config, job_id: u64) -> anyhow::Result<bool> {
+    let tmp_dir = tempdir().context("Create temp dir error")?;
+    let tmp_file = tmp_dir.path().join("artifact.zip");
+    let tmp_file_path = tmp_file.to_str().context("Error path to str")?;
+    let credential = config.job_credential()?;
+    let artifact_url = format!("{}/{}/artifacts", &config.jobs_api_url, job_id);
+    verbose!("download artifact {artifact_url} to {tmp_file_path}");
+    let download_ok = download_file(
+        &artifact_url,
+        tmp_file_path,
+        &credential,
+        &config.http_settings(),
+    )
+    .await?;
+    if download_ok {
+        extract_archive(tmp_file_path, &config.project_path)?;
+        verbose!("extract_artifacts is OK");
+    } else {
+        yellow("artifact not found");
+    }
+    Ok(download_ok)
+}
+
+async fn download_file(
+    url: &str,
+    file_path: &str,
+    credential: &Credential,
+    http_settings: &HttpSettings,
+) -> anyhow::Result<bool> {
     verbose!("download_file to {file_path}");
     let mut file = File::create(file_path).context("Error while creating downloaded file")?;
 
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_webpki_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
-    let client: hyper::Client<_, hyper::Body> = hyper::Client::builder().build(https);
-
-    let mut response = client
-        .get(url.parse().context("parse url error")?)
-        .await
-        .context("Error while request the file")?;
-    verbose!("download_file status {:?}", response.status());
-    if response.status().is_success() {
-        while let Some(chunk) = response.body_mut().data().await {
-            file.write_all(&chunk.context("Error while request the file")?)
+    let response = send(http_settings, || {
+        credential
+            .authorize(Request::get(url))
+            .body(Body::empty())
+            .context("parse url error")
+    })
+    .await
+    .context("Error while request the file")?;
+    let status = response.status();
+    verbose!("download_file status {status:?}");
+    if status.is_success() {
+        let mut body = response.into_body();
+        while let Some(chunk) = next_chunk(http_settings, &mut body)
+            .await
+            .context("Error while request the file")?
+        {
+            file.write_all(&chunk)
                 .context("Error while request the file")?;
         }
     }
-    Ok(response.status().is_success())
+    Ok(status.is_success())
 }
 
-fn extract_archive(archive_path: &str, output_path: &str) -> anyhow::Result<()> {
+pub fn extract_archive(archive_path: &str, output_path: &str) -> anyhow::Result<()> {
     verbose!("extract_archive {archive_path} to {output_path}");
     if archive_path.is_empty() {
         Err(anyhow!("archive_path is empty"))
@@ -83,8 +100,12 @@ fn extract_archive(archive_path: &str, output_path: &str) -> anyhow::Result<()>
 
 #[cfg(test)]
 mod tests {
-    use crate::artifact::{download_file, extract_archive, extract_artifacts};
+    use crate::artifact::{
+        download_file, extract_archive, extract_artifacts, extract_artifacts_of_job_id,
+    };
+    use crate::auth::Credential;
     use crate::config::Config;
+    use crate::http_client::tests::fast_http_settings;
     use crate::jobs::{GitlabCommit, GitlabJob};
     use httptest::{matchers::*, responders::*, Expectation, Server};
     use hyper::http;
@@ -105,7 +126,9 @@ mod tests {
         let tmp_dir = tempdir().unwrap();
         let tmp_path = tmp_dir.path().join("artifact.txt");
         let tmp_path_str = tmp_path.to_str().unwrap();
-        let res = download_file(&url, tmp_path_str).await.unwrap();
+        let res = download_file(&url, tmp_path_str, &Credential::None, &fast_http_settings())
+            .await
+            .unwrap();
         assert!(res);
         let content = fs::read_to_string(tmp_path_str).await.unwrap();
         assert_eq!(content, "abc");
@@ -122,7 +145,9 @@ mod tests {
         let tmp_dir = tempdir().unwrap();
         let tmp_path = tmp_dir.path().join("artifact.txt");
         let tmp_path_str = tmp_path.to_str().unwrap();
-        let res = download_file(&url, tmp_path_str).await.unwrap();
+        let res = download_file(&url, tmp_path_str, &Credential::None, &fast_http_settings())
+            .await
+            .unwrap();
         assert!(!res);
     }
 
@@ -131,10 +156,15 @@ mod tests {
         let tmp_dir = tempdir().unwrap();
         let tmp_path = tmp_dir.path().join("artifact.txt");
         let tmp_path_str = tmp_path.to_str().unwrap();
-        let res = download_file("http://localhost:45546/zzzzz/gitlab/api", tmp_path_str)
-            .await
-            .err()
-            .unwrap();
+        let res = download_file(
+            "http://localhost:45546/zzzzz/gitlab/api",
+            tmp_path_str,
+            &Credential::None,
+            &fast_http_settings(),
+        )
+        .await
+        .err()
+        .unwrap();
         assert_eq!(res.to_string(), "Error while request the file");
         assert_eq!(
             res.root_cause().to_string(),
@@ -144,11 +174,16 @@ mod tests {
 
     #[tokio::test]
     async fn test_download_file_ko() {
-        let err = download_file("url", "/zzzz/zzzz/zzzzz")
-            .await
-            .err()
-            .map(|e| format!("{e:#}"))
-            .unwrap();
+        let err = download_file(
+            "url",
+            "/zzzz/zzzz/zzzzz",
+            &Credential::None,
+            &fast_http_settings(),
+        )
+        .await
+        .err()
+        .map(|e| format!("{e:#}"))
+        .unwrap();
         assert_eq!(
             err,
             "Error while creating downloaded file: No such file or directory (os error 2)"
@@ -172,6 +207,7 @@ mod tests {
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 3,
             skip: false,
+            ..Default::default()
         };
         (tmp_dir, server, url, config)
     }
@@ -188,8 +224,12 @@ mod tests {
             .body(buffer)
             .unwrap();
         server.expect(
-            Expectation::matching(request::method_path("GET", "/api/123/jobs/456/artifacts"))
-                .respond_with(resp),
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/jobs/456/artifacts"),
+                request::headers(contains(("job-token", "__CI_JOB_TOKEN__"))),
+                request::query(len(eq(0))),
+            ])
+            .respond_with(resp),
         );
     }
 
@@ -243,6 +283,16 @@ mod tests {
             .unwrap());
     }
 
+    #[tokio::test]
+    async fn test_extract_artifacts_of_job_id() {
+        let (tmp_dir, server, _, config) = prepare_tmpdir_and_server();
+        add_artifact_expect(&server);
+        assert!(extract_artifacts_of_job_id(&config, 456).await.unwrap());
+        assert!(fs::try_exists(tmp_dir.path().join("artifact/folder1/d"))
+            .await
+            .unwrap());
+    }
+
     #[tokio::test]
     async fn test_extract_artifacts_not_found() {
         let (_, _, _, config) = prepare_tmpdir_and_server();
@@ -277,6 +327,7 @@ mod tests {
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 3,
             skip: false,
+            ..Default::default()
         };
         let job = GitlabJob {
             id: 456,
@@ -336,7 +387,7 @@ mod tests {
 
     #[test]
     fn test_extract_archive_out_err() {
-        let err = extract_archive("test/artifact.zip", "/zzz/zzz/zzz/zzz/zz")
+        let err = extract_archive("test/artifact.zip", "test/artifact.zip/zzz/zz")
             .err()
             .map(|e| format!("{e}"))
             .unwrap();
@@ -0,0 +1,91 @@
+use hyper::http::request::Builder;
+
+// the header of API_READ_TOKEN
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ApiAuth {
+    // PRIVATE-TOKEN : the project, group and personal access tokens
+    #[default]
+    PrivateToken,
+    // Authorization: Bearer : the OAuth2 tokens
+    Bearer,
+}
+
+// the credential of a request to Gitlab, sent in a header : the tokens in the query string end up
+// in the proxy and nginx access logs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credential {
+    PrivateToken(String),
+    JobToken(String),
+    Bearer(String),
+    None,
+}
+
+impl Credential {
+    // no header without token : the public projects
+    pub fn of_api_read_token(api_auth: ApiAuth, token: &str) -> Credential {
+        match api_auth {
+            _ if token.is_empty() => Credential::None,
+            ApiAuth::PrivateToken => Credential::PrivateToken(token.to_string()),
+            ApiAuth::Bearer => Credential::Bearer(token.to_string()),
+        }
+    }
+
+    // the name and the value of the header
+    pub fn header(&self) -> Option<(&'static str, String)> {
+        match self {
+            Credential::PrivateToken(token) => Some(("PRIVATE-TOKEN", token.clone())),
+            Credential::JobToken(token) => Some(("JOB-TOKEN", token.clone())),
+            Credential::Bearer(token) => Some(("Authorization", format!("Bearer {token}"))),
+            Credential::None => None,
+        }
+    }
+
+    // add the header to the request
+    pub fn authorize(&self, builder: Builder) -> Builder {
+        match self.header() {
+            Some((name, value)) => builder.header(name, value),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{ApiAuth, Credential};
+    use hyper::Request;
+
+    #[test]
+    fn test_credential_header() {
+        assert_eq!(
+            Credential::of_api_read_token(ApiAuth::PrivateToken, "aaa").header(),
+            Some(("PRIVATE-TOKEN", "aaa".to_string()))
+        );
+        assert_eq!(
+            Credential::of_api_read_token(ApiAuth::Bearer, "aaa").header(),
+            Some(("Authorization", "Bearer aaa".to_string()))
+        );
+        assert_eq!(
+            Credential::JobToken("bbb".to_string()).header(),
+            Some(("JOB-TOKEN", "bbb".to_string()))
+        );
+        assert_eq!(
+            Credential::of_api_read_token(ApiAuth::Bearer, ""),
+            Credential::None
+        );
+        assert_eq!(Credential::None.header(), None);
+    }
+
+    #[test]
+    fn test_credential_authorize() {
+        let request = Credential::JobToken("bbb".to_string())
+            .authorize(Request::get("http://localhost/"))
+            .body(())
+            .unwrap();
+        assert_eq!(request.headers()["job-token"], "bbb");
+        let request = Credential::None
+            .authorize(Request::get("http://localhost/"))
+            .body(())
+            .unwrap();
+        assert!(request.headers().is_empty());
+    }
+}
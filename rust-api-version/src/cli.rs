@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Context};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Command {
+    // search the fingerprint of the HEAD and write the ci-skip file, the default command
+    #[default]
+    Check,
+    // print the fingerprint of the HEAD
+    Fingerprint,
+    // explain why the jobs match or not
+    Explain,
+    // add the fingerprint of the HEAD to the history store, in the after_script
+    Record,
+    // check the config, the repository and the backend
+    Doctor,
+    Help,
+}
+
+impl Command {
+    // check and record run in a job : CI_JOB_ID is required
+    pub fn requires_ci_job(&self) -> bool {
+        matches!(self, Command::Check | Command::Record)
+    }
+
+    // fingerprint runs outside of Gitlab : the API and the CI_* variables are not required
+    pub fn requires_api(&self) -> bool {
+        !matches!(self, Command::Fingerprint | Command::Help)
+    }
+}
+
+// the output of the fingerprint command
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+fn parse_format(value: &str) -> anyhow::Result<OutputFormat> {
+    match value {
+        "text" => Ok(OutputFormat::Text),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(anyhow!("--format must be text or json : {value}")),
+    }
+}
+
+// the command and the flags that override the environment variables
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CliArgs {
+    pub command: Command,
+    // --job : CI_JOB_NAME
+    pub job: Option<String>,
+    // --paths : SKIP_IF_TREE_OK_IN_PAST
+    pub paths: Option<String>,
+    // --project-dir : CI_PROJECT_DIR
+    pub project_dir: Option<String>,
+    // --project-id : CI_PROJECT_ID
+    pub project_id: Option<String>,
+    // --api-url : CI_API_V4_URL
+    pub api_url: Option<String>,
+    // --commit : the commit-ish of the fingerprint command, HEAD by default
+    pub commit: Option<String>,
+    // --format : the output of the fingerprint command
+    pub format: OutputFormat,
+    // --dry-run : SKIP_CI_DRY_RUN=true
+    pub dry_run: bool,
+}
+
+impl CliArgs {
+    // the environment variables overridden by the flags
+    pub fn get_overrides(&self) -> Vec<(&'static str, String)> {
+        [
+            ("CI_JOB_NAME", &self.job),
+            ("SKIP_IF_TREE_OK_IN_PAST", &self.paths),
+            ("CI_PROJECT_DIR", &self.project_dir),
+            ("CI_PROJECT_ID", &self.project_id),
+            ("CI_API_V4_URL", &self.api_url),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.clone().map(|value| (name, value)))
+        .chain(
+            self.dry_run
+                .then(|| ("SKIP_CI_DRY_RUN", "true".to_string())),
+        )
+        .collect()
+    }
+}
+
+fn parse_command(value: &str) -> anyhow::Result<Command> {
+    match value {
+        "check" => Ok(Command::Check),
+        "fingerprint" => Ok(Command::Fingerprint),
+        "explain" => Ok(Command::Explain),
+        "record" => Ok(Command::Record),
+        "doctor" => Ok(Command::Doctor),
+        "help" => Ok(Command::Help),
+        _ => Err(anyhow!("unknown command : {value}")),
+    }
+}
+
+// parse the arguments (without the program name) : [command] [--flag value | --flag=value]...
+pub fn parse_args(args: &[String]) -> anyhow::Result<CliArgs> {
+    let mut cli_args = CliArgs::default();
+    let mut command = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            cli_args.command = Command::Help;
+            return Ok(cli_args);
+        }
+        if arg == "--dry-run" {
+            cli_args.dry_run = true;
+            continue;
+        }
+        let Some(flag) = arg.strip_prefix("--") else {
+            if command.is_some() {
+                return Err(anyhow!("unexpected argument : {arg}"));
+            }
+            command = Some(parse_command(arg)?);
+            continue;
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, value.to_string()),
+            None => (
+                flag,
+                args.next()
+                    .with_context(|| format!("missing value of --{flag}"))?
+                    .clone(),
+            ),
+        };
+        if name == "format" {
+            cli_args.format = parse_format(&value)?;
+            continue;
+        }
+        let field = match name {
+            "job" => &mut cli_args.job,
+            "paths" => &mut cli_args.paths,
+            "project-dir" => &mut cli_args.project_dir,
+            "project-id" => &mut cli_args.project_id,
+            "api-url" => &mut cli_args.api_url,
+            "commit" => &mut cli_args.commit,
+            _ => return Err(anyhow!("unknown flag : --{name}")),
+        };
+        *field = Some(value);
+    }
+    cli_args.command = command.unwrap_or_default();
+    Ok(cli_args)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::{parse_args, CliArgs, Command, OutputFormat};
+
+    fn parse(args: &[&str]) -> anyhow::Result<CliArgs> {
+        parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn test_parse_args_default() {
+        assert_eq!(parse(&[]).unwrap(), CliArgs::default());
+        assert_eq!(parse(&[]).unwrap().command, Command::Check);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let cli_args = parse(&[
+            "--job",
+            "test 1/3",
+            "fingerprint",
+            "--paths=service-A !service-A/docs",
+            "--project-dir",
+            ".",
+            "--project-id=123",
+            "--api-url",
+            "https://gitlab.example.com/api/v4",
+            "--commit",
+            "main~2",
+            "--format=json",
+            "--dry-run",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli_args,
+            CliArgs {
+                command: Command::Fingerprint,
+                job: Some("test 1/3".to_string()),
+                paths: Some("service-A !service-A/docs".to_string()),
+                project_dir: Some(".".to_string()),
+                project_id: Some("123".to_string()),
+                api_url: Some("https://gitlab.example.com/api/v4".to_string()),
+                commit: Some("main~2".to_string()),
+                format: OutputFormat::Json,
+                dry_run: true,
+            }
+        );
+        assert_eq!(
+            cli_args.get_overrides(),
+            vec![
+                ("CI_JOB_NAME", "test 1/3".to_string()),
+                (
+                    "SKIP_IF_TREE_OK_IN_PAST",
+                    "service-A !service-A/docs".to_string()
+                ),
+                ("CI_PROJECT_DIR", ".".to_string()),
+                ("CI_PROJECT_ID", "123".to_string()),
+                (
+                    "CI_API_V4_URL",
+                    "https://gitlab.example.com/api/v4".to_string()
+                ),
+                ("SKIP_CI_DRY_RUN", "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_args_commands() {
+        assert_eq!(parse(&["check"]).unwrap().command, Command::Check);
+        assert_eq!(parse(&["explain"]).unwrap().command, Command::Explain);
+        assert_eq!(parse(&["record"]).unwrap().command, Command::Record);
+        assert_eq!(parse(&["doctor"]).unwrap().command, Command::Doctor);
+        assert_eq!(parse(&["help"]).unwrap().command, Command::Help);
+        assert_eq!(parse(&["record", "-h"]).unwrap().command, Command::Help);
+        assert_eq!(parse(&["--help"]).unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn test_parse_args_ko() {
+        let err = |args: &[&str]| parse(args).err().unwrap().to_string();
+        assert_eq!(err(&["run"]), "unknown command : run");
+        assert_eq!(err(&["check", "record"]), "unexpected argument : record");
+        assert_eq!(err(&["--jobs", "a"]), "unknown flag : --jobs");
+        assert_eq!(err(&["check", "--job"]), "missing value of --job");
+        assert_eq!(
+            err(&["fingerprint", "--format", "yaml"]),
+            "--format must be text or json : yaml"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use crate::verbose;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::env::VarError;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = ".skip-if-tree-ok-in-past.toml";
+
+// the TOML config file, or the YAML one if its extension is .yml or .yaml
+fn parse_config_file(path: &Path, content: &str) -> anyhow::Result<ConfigFile> {
+    let error = || format!("{} error", path.display());
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yml" | "yaml") => serde_yaml::from_str::<ConfigFile>(content).with_context(error),
+        _ => toml::from_str::<ConfigFile>(content).with_context(error),
+    }
+}
+
+// a section of the config file : [default] or [jobs."<CI_JOB_NAME>"], the tokens and secrets are
+// not allowed
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFileSection {
+    pub paths: Option<Vec<String>>,
+    pub excludes: Option<Vec<String>>,
+    pub backend: Option<String>,
+    pub job_name_match: Option<String>,
+    pub job_name_regex: Option<String>,
+    pub job_name_aliases: Option<Vec<String>>,
+    pub api_auth: Option<String>,
+    pub page_to_fetch_max: Option<u32>,
+    pub page_prefetch_max: Option<u32>,
+    pub pagination: Option<String>,
+    pub commit_to_check_same_ref_max: Option<u32>,
+    pub commit_to_check_same_job_max: Option<u32>,
+    pub search: Option<String>,
+    pub ancestor_to_check_max: Option<u32>,
+    pub ancestor_query_max: Option<u32>,
+    pub fingerprint_hash: Option<String>,
+    pub no_artifact: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub skip: Option<bool>,
+    pub git_notes_ref: Option<String>,
+    pub git_notes_remote: Option<String>,
+    pub fs_root: Option<String>,
+    pub fs_max_age_days: Option<u64>,
+    pub s3_endpoint: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_prefix: Option<String>,
+    pub s3_region: Option<String>,
+    pub s3_artifact_paths: Option<Vec<String>>,
+    pub http_connect_timeout: Option<u64>,
+    pub http_read_timeout: Option<u64>,
+    pub http_retry_max: Option<u32>,
+    pub ca_file: Option<String>,
+    pub tls_cert_file: Option<String>,
+    pub tls_key_file: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub default: ConfigFileSection,
+    #[serde(default)]
+    pub jobs: HashMap<String, ConfigFileSection>,
+}
+
+impl ConfigFileSection {
+    // the fields of the section override the fields of the base section
+    fn over(self, base: ConfigFileSection) -> ConfigFileSection {
+        ConfigFileSection {
+            paths: self.paths.or(base.paths),
+            excludes: self.excludes.or(base.excludes),
+            backend: self.backend.or(base.backend),
+            job_name_match: self.job_name_match.or(base.job_name_match),
+            job_name_regex: self.job_name_regex.or(base.job_name_regex),
+            job_name_aliases: self.job_name_aliases.or(base.job_name_aliases),
+            api_auth: self.api_auth.or(base.api_auth),
+            page_to_fetch_max: self.page_to_fetch_max.or(base.page_to_fetch_max),
+            page_prefetch_max: self.page_prefetch_max.or(base.page_prefetch_max),
+            pagination: self.pagination.or(base.pagination),
+            commit_to_check_same_ref_max: self
+                .commit_to_check_same_ref_max
+                .or(base.commit_to_check_same_ref_max),
+            commit_to_check_same_job_max: self
+                .commit_to_check_same_job_max
+                .or(base.commit_to_check_same_job_max),
+            search: self.search.or(base.search),
+            ancestor_to_check_max: self.ancestor_to_check_max.or(base.ancestor_to_check_max),
+            ancestor_query_max: self.ancestor_query_max.or(base.ancestor_query_max),
+            fingerprint_hash: self.fingerprint_hash.or(base.fingerprint_hash),
+            no_artifact: self.no_artifact.or(base.no_artifact),
+            dry_run: self.dry_run.or(base.dry_run),
+            skip: self.skip.or(base.skip),
+            git_notes_ref: self.git_notes_ref.or(base.git_notes_ref),
+            git_notes_remote: self.git_notes_remote.or(base.git_notes_remote),
+            fs_root: self.fs_root.or(base.fs_root),
+            fs_max_age_days: self.fs_max_age_days.or(base.fs_max_age_days),
+            s3_endpoint: self.s3_endpoint.or(base.s3_endpoint),
+            s3_bucket: self.s3_bucket.or(base.s3_bucket),
+            s3_prefix: self.s3_prefix.or(base.s3_prefix),
+            s3_region: self.s3_region.or(base.s3_region),
+            s3_artifact_paths: self.s3_artifact_paths.or(base.s3_artifact_paths),
+            http_connect_timeout: self.http_connect_timeout.or(base.http_connect_timeout),
+            http_read_timeout: self.http_read_timeout.or(base.http_read_timeout),
+            http_retry_max: self.http_retry_max.or(base.http_retry_max),
+            ca_file: self.ca_file.or(base.ca_file),
+            tls_cert_file: self.tls_cert_file.or(base.tls_cert_file),
+            tls_key_file: self.tls_key_file.or(base.tls_key_file),
+        }
+    }
+
+    // the environment variables equivalent to the section
+    fn into_vars(self) -> HashMap<&'static str, String> {
+        let mut vars = HashMap::new();
+        let mut insert = |name: &'static str, value: Option<String>| {
+            if let Some(value) = value {
+                vars.insert(name, value);
+            }
+        };
+        let to_string = |value: Option<u32>| value.map(|v| v.to_string());
+        insert(
+            "SKIP_IF_TREE_OK_IN_PAST",
+            self.paths.map(|paths| paths.join(" ")),
+        );
+        // kept apart from the paths : SKIP_IF_TREE_OK_IN_PAST may be overridden by the job
+        insert(
+            "SKIP_CI_EXCLUDES",
+            self.excludes.map(|excludes| excludes.join(" ")),
+        );
+        insert("SKIP_CI_BACKEND", self.backend);
+        insert("SKIP_CI_JOB_NAME_MATCH", self.job_name_match);
+        insert("SKIP_CI_JOB_NAME_REGEX", self.job_name_regex);
+        insert(
+            "SKIP_CI_JOB_NAME_ALIASES",
+            self.job_name_aliases.map(|aliases| aliases.join(";")),
+        );
+        insert("SKIP_CI_API_AUTH", self.api_auth);
+        insert(
+            "SKIP_CI_PAGE_TO_FETCH_MAX",
+            to_string(self.page_to_fetch_max),
+        );
+        insert(
+            "SKIP_CI_PAGE_PREFETCH_MAX",
+            to_string(self.page_prefetch_max),
+        );
+        insert("SKIP_CI_PAGINATION", self.pagination);
+        insert(
+            "SKIP_CI_COMMIT_TO_CHECK_SAME_REF_MAX",
+            to_string(self.commit_to_check_same_ref_max),
+        );
+        insert(
+            "SKIP_CI_COMMIT_TO_CHECK_SAME_JOB_MAX",
+            to_string(self.commit_to_check_same_job_max),
+        );
+        insert("SKIP_CI_SEARCH", self.search);
+        insert(
+            "SKIP_CI_ANCESTOR_TO_CHECK_MAX",
+            to_string(self.ancestor_to_check_max),
+        );
+        insert(
+            "SKIP_CI_ANCESTOR_QUERY_MAX",
+            to_string(self.ancestor_query_max),
+        );
+        insert("SKIP_CI_FINGERPRINT_HASH", self.fingerprint_hash);
+        insert(
+            "SKIP_CI_NO_ARTIFACT",
+            self.no_artifact.map(|v| v.to_string()),
+        );
+        insert("SKIP_CI_DRY_RUN", self.dry_run.map(|v| v.to_string()));
+        insert("SKIP_SKIP_CI", self.skip.map(|v| v.to_string()));
+        insert("SKIP_CI_GIT_NOTES_REF", self.git_notes_ref);
+        insert("SKIP_CI_GIT_NOTES_REMOTE", self.git_notes_remote);
+        insert("SKIP_CI_FS_ROOT", self.fs_root);
+        insert(
+            "SKIP_CI_FS_MAX_AGE_DAYS",
+            self.fs_max_age_days.map(|v| v.to_string()),
+        );
+        insert("SKIP_CI_S3_ENDPOINT", self.s3_endpoint);
+        insert("SKIP_CI_S3_BUCKET", self.s3_bucket);
+        insert("SKIP_CI_S3_PREFIX", self.s3_prefix);
+        insert("SKIP_CI_S3_REGION", self.s3_region);
+        insert(
+            "SKIP_CI_S3_ARTIFACT_PATHS",
+            self.s3_artifact_paths.map(|paths| paths.join(" ")),
+        );
+        insert(
+            "SKIP_CI_HTTP_CONNECT_TIMEOUT",
+            self.http_connect_timeout.map(|v| v.to_string()),
+        );
+        insert(
+            "SKIP_CI_HTTP_READ_TIMEOUT",
+            self.http_read_timeout.map(|v| v.to_string()),
+        );
+        insert("SKIP_CI_HTTP_RETRY_MAX", to_string(self.http_retry_max));
+        insert("SKIP_CI_CA_FILE", self.ca_file);
+        insert("SKIP_CI_TLS_CERT_FILE", self.tls_cert_file);
+        insert("SKIP_CI_TLS_KEY_FILE", self.tls_key_file);
+        vars
+    }
+}
+
+// the variables of the config : the flags of the command line override the environment
+// variables, the environment variables override the config file
+#[derive(Debug, Default)]
+pub struct ConfigVars {
+    overrides: HashMap<&'static str, String>,
+    file_vars: HashMap<&'static str, String>,
+}
+
+impl ConfigVars {
+    pub fn with_overrides(self, overrides: Vec<(&'static str, String)>) -> ConfigVars {
+        ConfigVars {
+            overrides: overrides.into_iter().collect(),
+            ..self
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Result<String, VarError> {
+        match self.overrides.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => env::var(name).or_else(|e| self.file_vars.get(name).cloned().ok_or(e)),
+        }
+    }
+}
+
+// read the [default] section and the [jobs."<job_name>"] section of the config file
+pub fn read_config_file(path: &Path, job_name: &str) -> anyhow::Result<ConfigVars> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            verbose!("{path:?} not found");
+            return Ok(ConfigVars::default());
+        }
+        Err(e) => return Err(e).with_context(|| format!("read {path:?} error")),
+    };
+    let mut config_file = parse_config_file(path, &content)?;
+    let section = match config_file.jobs.remove(job_name) {
+        Some(job_section) => {
+            verbose!("{} : [jobs.\"{job_name}\"] section found", path.display());
+            job_section.over(config_file.default)
+        }
+        None => config_file.default,
+    };
+    Ok(ConfigVars {
+        file_vars: section.into_vars(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config_file::read_config_file;
+    use std::env::VarError;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const CONFIG: &str = r#"
+[default]
+backend = "cache"
+page_to_fetch_max = 10
+excludes = ["**/*.md"]
+
+[jobs."SERVICE-A"]
+paths = ["service-A", "LIB-1"]
+excludes = ["service-A/docs"]
+no_artifact = true
+
+[jobs."SERVICE-B"]
+paths = ["service-B"]
+backend = "api"
+job_name_aliases = ["SERVICE-B-OLD", "build: [linux, amd64]"]
+"#;
+
+    #[test]
+    fn test_read_config_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join(".skip-if-tree-ok-in-past.toml");
+        fs::write(&path, CONFIG).unwrap();
+        temp_env::with_vars(
+            [
+                ("SKIP_IF_TREE_OK_IN_PAST", None::<&str>),
+                ("SKIP_CI_EXCLUDES", None),
+                ("SKIP_CI_BACKEND", None),
+                ("SKIP_CI_PAGE_TO_FETCH_MAX", Some("3")),
+                ("SKIP_CI_NO_ARTIFACT", None),
+                ("SKIP_CI_JOB_NAME_ALIASES", None),
+            ],
+            || {
+                let vars = read_config_file(&path, "SERVICE-A").unwrap();
+                assert_eq!(
+                    vars.var("SKIP_IF_TREE_OK_IN_PAST").unwrap(),
+                    "service-A LIB-1"
+                );
+                assert_eq!(vars.var("SKIP_CI_EXCLUDES").unwrap(), "service-A/docs");
+                assert_eq!(vars.var("SKIP_CI_BACKEND").unwrap(), "cache");
+                // the environment variables override the file
+                assert_eq!(vars.var("SKIP_CI_PAGE_TO_FETCH_MAX").unwrap(), "3");
+                assert_eq!(vars.var("SKIP_CI_NO_ARTIFACT").unwrap(), "true");
+                assert_eq!(vars.var("SKIP_CI_FS_ROOT"), Err(VarError::NotPresent));
+
+                let vars = read_config_file(&path, "SERVICE-B").unwrap();
+                assert_eq!(vars.var("SKIP_IF_TREE_OK_IN_PAST").unwrap(), "service-B");
+                assert_eq!(vars.var("SKIP_CI_EXCLUDES").unwrap(), "**/*.md");
+                assert_eq!(vars.var("SKIP_CI_BACKEND").unwrap(), "api");
+                assert_eq!(vars.var("SKIP_CI_NO_ARTIFACT"), Err(VarError::NotPresent));
+                assert_eq!(
+                    vars.var("SKIP_CI_JOB_NAME_ALIASES").unwrap(),
+                    "SERVICE-B-OLD;build: [linux, amd64]"
+                );
+
+                let vars = read_config_file(&path, "SERVICE-C").unwrap();
+                assert_eq!(
+                    vars.var("SKIP_IF_TREE_OK_IN_PAST"),
+                    Err(VarError::NotPresent)
+                );
+                assert_eq!(vars.var("SKIP_CI_EXCLUDES").unwrap(), "**/*.md");
+                assert_eq!(vars.var("SKIP_CI_BACKEND").unwrap(), "cache");
+
+                // the flags override the environment variables
+                let vars =
+                    vars.with_overrides(vec![("SKIP_CI_PAGE_TO_FETCH_MAX", "7".to_string())]);
+                assert_eq!(vars.var("SKIP_CI_PAGE_TO_FETCH_MAX").unwrap(), "7");
+            },
+        );
+        // the paths of the environment keep the excludes of the file
+        temp_env::with_vars(
+            [
+                ("SKIP_IF_TREE_OK_IN_PAST", Some("service-A")),
+                ("SKIP_CI_EXCLUDES", None),
+            ],
+            || {
+                let vars = read_config_file(&path, "SERVICE-A").unwrap();
+                assert_eq!(vars.var("SKIP_IF_TREE_OK_IN_PAST").unwrap(), "service-A");
+                assert_eq!(vars.var("SKIP_CI_EXCLUDES").unwrap(), "service-A/docs");
+            },
+        );
+    }
+
+    #[test]
+    fn test_read_config_file_yaml() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join(".skip-if-tree-ok-in-past.yml");
+        fs::write(
+            &path,
+            r#"
+default:
+  backend: cache
+  excludes: ["**/*.md"]
+jobs:
+  SERVICE-A:
+    paths: [service-A, LIB-1]
+    page_to_fetch_max: 10
+"#,
+        )
+        .unwrap();
+        temp_env::with_vars(
+            [
+                ("SKIP_IF_TREE_OK_IN_PAST", None::<&str>),
+                ("SKIP_CI_EXCLUDES", None),
+                ("SKIP_CI_BACKEND", None),
+                ("SKIP_CI_PAGE_TO_FETCH_MAX", None),
+            ],
+            || {
+                let vars = read_config_file(&path, "SERVICE-A").unwrap();
+                assert_eq!(
+                    vars.var("SKIP_IF_TREE_OK_IN_PAST").unwrap(),
+                    "service-A LIB-1"
+                );
+                assert_eq!(vars.var("SKIP_CI_EXCLUDES").unwrap(), "**/*.md");
+                assert_eq!(vars.var("SKIP_CI_BACKEND").unwrap(), "cache");
+                assert_eq!(vars.var("SKIP_CI_PAGE_TO_FETCH_MAX").unwrap(), "10");
+            },
+        );
+    }
+
+    #[test]
+    fn test_read_config_file_not_found() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join(".skip-if-tree-ok-in-past.toml");
+        temp_env::with_var("SKIP_CI_BACKEND", None::<&str>, || {
+            let vars = read_config_file(&path, "SERVICE-A").unwrap();
+            assert_eq!(vars.var("SKIP_CI_BACKEND"), Err(VarError::NotPresent));
+        });
+    }
+
+    #[test]
+    fn test_read_config_file_ko() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join(".skip-if-tree-ok-in-past.toml");
+        fs::write(&path, "[default]\napi_read_token = \"secret\"\n").unwrap();
+        let err = read_config_file(&path, "SERVICE-A").err().unwrap();
+        assert!(format!("{err:#}").starts_with(&format!(
+            "{} error: unknown field `api_read_token`",
+            path.display()
+        )));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::{Backend, Config};
+use crate::find_last_job_ok::{get_paths_to_check, open_repository};
+use crate::git::get_tree_of_paths;
+use crate::git_notes::check_git_notes;
+use crate::history::find_in_history;
+use crate::jobs::JobPages;
+use crate::skipci_log::{green, red};
+use crate::verbose;
+use anyhow::Context;
+use std::path::Path;
+
+// a fingerprint that is never recorded
+const DOCTOR_FINGERPRINT: &str = "0000000000000000000000000000000000000000";
+
+// the repository is readable and the paths to check exist in the HEAD
+fn check_repository(config: &Config) -> anyhow::Result<String> {
+    let repo = open_repository(config)?;
+    let head = repo
+        .refname_to_id("HEAD")
+        .context("Head retrieving error")?;
+    let paths_to_check = get_paths_to_check(config)?;
+    let paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
+    let tree = get_tree_of_paths(&repo, &head.to_string(), &paths)?;
+    let absent_count = tree.entries.iter().filter(|e| e.filemode == 0).count();
+    let fingerprint = tree.get_fingerprint(config.fingerprint_hash)?;
+    Ok(format!(
+        "HEAD {head} : {} paths ({absent_count} absent), fingerprint {fingerprint}",
+        tree.entries.len()
+    ))
+}
+
+// the backend answers a lookup
+async fn check_backend(config: &Config) -> anyhow::Result<String> {
+    match config.backend {
+        Backend::Api => {
+            let jobs = JobPages::new(config).next().await?.unwrap_or_default();
+            Ok(format!(
+                "{:?} backend : {} successful jobs in the first page",
+                config.backend,
+                jobs.len()
+            ))
+        }
+        Backend::GitNotes => Ok(format!(
+            "{:?} backend : {}",
+            config.backend,
+            check_git_notes(config)?
+        )),
+        _ => {
+            find_in_history(config, DOCTOR_FINGERPRINT).await?;
+            Ok(format!("{:?} backend : lookup OK", config.backend))
+        }
+    }
+}
+
+// check the config, the repository and the backend, exit 0 if everything is OK
+pub async fn doctor_with_exit_code(config_result: anyhow::Result<Config>) -> i32 {
+    let config = match config_result {
+        Ok(config) => {
+            verbose!("{config}");
+            green(&format!(
+                "✅ config : job {}, paths {}",
+                config.ci_job_name, config.files_to_check
+            ));
+            config
+        }
+        Err(e) => {
+            red(&format!("❌ CONFIG ERROR : \n{e:#?}"));
+            return 6;
+        }
+    };
+    let results = [
+        ("repository", check_repository(&config)),
+        ("backend", check_backend(&config).await),
+    ];
+    let mut exit_code = 0;
+    for (name, result) in results {
+        match result {
+            Ok(msg) => green(&format!("✅ {name} : {msg}")),
+            Err(e) => {
+                red(&format!("❌ {name} ERROR : \n{e:#?}"));
+                exit_code = 2;
+            }
+        }
+    }
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::doctor::{check_backend, check_repository, doctor_with_exit_code};
+    use crate::process::tests::{add_jobs_expect, create_config_ok, prepare_tmp_repo};
+    use anyhow::Error;
+    use httptest::Server;
+    use std::fs;
+
+    #[test]
+    fn test_check_repository() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            files_to_check: "root-1 Service-A/file-A1 not-found".to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        let msg = check_repository(&config).unwrap();
+        assert!(
+            msg.starts_with("HEAD 26b244f55f8886ccf6a47ea7f24498e9801fc38f : 3 paths (1 absent)")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_backend_api() {
+        let server = Server::run();
+        add_jobs_expect(&server);
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"));
+        let msg = check_backend(&config).await.unwrap();
+        assert!(msg.starts_with("Api backend : "));
+    }
+
+    #[tokio::test]
+    async fn test_doctor_with_exit_code() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        fs::write(&history_path, "abc:1\n").unwrap();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        assert_eq!(doctor_with_exit_code(Ok(config.clone())).await, 0);
+        let config = Config {
+            files_to_check: "".to_string(),
+            ..config
+        };
+        assert_eq!(doctor_with_exit_code(Ok(config)).await, 2);
+        assert_eq!(doctor_with_exit_code(Err(Error::msg("error"))).await, 6);
+    }
+}
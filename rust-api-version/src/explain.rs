@@ -0,0 +1,417 @@
+use crate::ancestors::{scan_ancestor_jobs, Search};
+use crate::config::{Backend, Config};
+use crate::explain::JobMatch::{
+    CommitMissing, DifferentName, Error, Match, NotSuccessful, PathsDiffer,
+};
+use crate::find_last_job_ok::{
+    get_paths_to_check, is_job_name_ok, open_repository, scan_last_jobs,
+};
+use crate::git::{get_diff_stat, get_tree_of_paths, TreeOfPaths};
+use crate::jobs::GitlabJob;
+use crate::process::search;
+use crate::process::ProcessResult::{HistoryFound, JobFound};
+use crate::skipci_log::{green, red, yellow};
+use crate::verbose;
+use anyhow::Context;
+use git2::{Oid, Repository};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+// why a job of the project matches the HEAD or not
+#[derive(Debug, PartialEq)]
+pub enum JobMatch {
+    Match,
+    DifferentName(String),
+    NotSuccessful(String),
+    // the commit of the job is not in the clone : GIT_DEPTH too low ?
+    CommitMissing,
+    PathsDiffer(Vec<String>),
+    Error(String),
+}
+
+impl Display for JobMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Match => write!(f, "✅ same tree"),
+            DifferentName(name) => write!(f, "different name : {name}"),
+            NotSuccessful(status) => write!(f, "not successful : {status}"),
+            CommitMissing => write!(f, "commit missing from the clone (GIT_DEPTH ?)"),
+            PathsDiffer(paths) => write!(f, "paths differing : {}", paths.join(", ")),
+            Error(e) => write!(f, "error : {e}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ScannedJob {
+    pub job: GitlabJob,
+    pub job_match: JobMatch,
+}
+
+impl Display for ScannedJob {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "job {} ref={} commit={} : {}",
+            self.job.id,
+            self.job.job_ref,
+            self.job.commit.id.get(..8).unwrap_or(&self.job.commit.id),
+            self.job_match
+        )
+    }
+}
+
+fn explain_job(
+    config: &Config,
+    repo: &Repository,
+    paths: &Vec<&Path>,
+    tree_of_head: &TreeOfPaths,
+    job: &GitlabJob,
+) -> JobMatch {
+    if !is_job_name_ok(config, &job.name) {
+        return DifferentName(job.name.clone());
+    }
+    if job.status != "success" {
+        return NotSuccessful(job.status.clone());
+    }
+    let commit_found = Oid::from_str(&job.commit.id).is_ok_and(|oid| repo.find_commit(oid).is_ok());
+    if !commit_found {
+        return CommitMissing;
+    }
+    match get_tree_of_paths(repo, &job.commit.id, paths) {
+        Ok(tree) => {
+            let differing_paths = tree.get_differing_paths(tree_of_head);
+            if differing_paths.is_empty() {
+                Match
+            } else {
+                PathsDiffer(differing_paths)
+            }
+        }
+        Err(e) => Error(format!("{e:#}")),
+    }
+}
+
+// scan the jobs like find_last_job_ok, with the same SKIP_CI_SEARCH and the same limits, and
+// explain the match of each job
+async fn scan_jobs(
+    config: &Config,
+    repo: &Repository,
+    paths: &Vec<&Path>,
+    tree_of_head: &TreeOfPaths,
+) -> anyhow::Result<Vec<ScannedJob>> {
+    let mut scanned_jobs = Vec::new();
+    let explain = |job: &GitlabJob| {
+        let job_match = explain_job(config, repo, paths, tree_of_head, job);
+        let is_match = job_match == Match;
+        scanned_jobs.push(ScannedJob {
+            job: job.clone(),
+            job_match,
+        });
+        is_match
+    };
+    match config.search {
+        Search::Jobs => scan_last_jobs(config, explain).await?,
+        Search::Ancestors => {
+            let fingerprint_of_head = tree_of_head.get_fingerprint(config.fingerprint_hash)?;
+            scan_ancestor_jobs(config, repo, paths, &fingerprint_of_head, explain).await?
+        }
+    };
+    Ok(scanned_jobs)
+}
+
+// the job whose tree has the fewest paths differing from the HEAD
+fn get_closest_candidate(scanned_jobs: &[ScannedJob]) -> Option<&ScannedJob> {
+    scanned_jobs
+        .iter()
+        .filter_map(|scanned_job| match &scanned_job.job_match {
+            PathsDiffer(paths) => Some((paths.len(), scanned_job)),
+            _ => None,
+        })
+        .min_by_key(|(count, _)| *count)
+        .map(|(_, scanned_job)| scanned_job)
+}
+
+// list the jobs scanned with the api backend, return true if a job matches
+async fn explain_api(config: &Config) -> anyhow::Result<bool> {
+    let repo = open_repository(config)?;
+    let paths_to_check = get_paths_to_check(config)?;
+    let paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
+    let head = repo
+        .refname_to_id("HEAD")
+        .context("Head retrieving error")?
+        .to_string();
+    let tree_of_head = get_tree_of_paths(&repo, &head, &paths)?;
+    println!("HEAD {head} :\n{tree_of_head}");
+
+    let scanned_jobs = scan_jobs(config, &repo, &paths, &tree_of_head).await?;
+    for scanned_job in &scanned_jobs {
+        println!("{scanned_job}");
+    }
+    if let Some(scanned_job) = scanned_jobs.iter().find(|j| j.job_match == Match) {
+        green(&format!(
+            "✅ tree found in job {}  ",
+            scanned_job.job.web_url
+        ));
+        return Ok(true);
+    }
+    yellow(&format!(
+        "❌ tree not found in the {} jobs scanned",
+        scanned_jobs.len()
+    ));
+    if let Some(closest) = get_closest_candidate(&scanned_jobs) {
+        let diff_stat = get_diff_stat(&repo, &closest.job.commit.id, &head, &paths)?;
+        println!(
+            "the closest candidate : job {} {}\n{diff_stat}",
+            closest.job.id, closest.job.web_url
+        );
+    }
+    Ok(false)
+}
+
+// search the fingerprint of the HEAD in the history store with the verbose log, without the
+// artifacts, the ci-skip file and the record of the cache backend
+async fn explain_history(config: &Config) -> anyhow::Result<bool> {
+    let config = Config {
+        no_artifact: true,
+        ..config.clone()
+    };
+    match search(&config, false).await? {
+        JobFound(job, _) => {
+            green(&format!("✅ tree found in job {}  ", &job.web_url));
+            Ok(true)
+        }
+        HistoryFound(record) => {
+            green(&format!("✅ tree found in the history : {record}"));
+            Ok(true)
+        }
+        result => {
+            verbose!("result = {result:?}");
+            yellow("❌ tree not found in the history");
+            Ok(false)
+        }
+    }
+}
+
+// explain why the tree is found or not, without side effect : exit 0 if found, 1 if not found
+pub async fn explain_with_exit_code(config_result: anyhow::Result<Config>) -> i32 {
+    match config_result {
+        Ok(config) => {
+            verbose!("{config}");
+            let result = match config.backend {
+                Backend::Api => explain_api(&config).await,
+                _ => explain_history(&config).await,
+            };
+            match result {
+                Ok(true) => 0,
+                Ok(false) => 1,
+                Err(e) => {
+                    red(&format!("❌ EXPLAIN ERROR : \n{e:#?}"));
+                    2
+                }
+            }
+        }
+        Err(e) => {
+            red(&format!("❌ CONFIG ERROR : \n{e:#?}"));
+            6
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ancestors::tests::{
+        add_pipeline_jobs_expect, add_pipelines_expect, COMMIT_03, COMMIT_04,
+    };
+    use crate::ancestors::Search;
+    use crate::config::{Backend, Config};
+    use crate::explain::JobMatch::{
+        CommitMissing, DifferentName, Match, NotSuccessful, PathsDiffer,
+    };
+    use crate::explain::{explain_with_exit_code, get_closest_candidate, scan_jobs};
+    use crate::find_last_job_ok::{get_paths_to_check, open_repository};
+    use crate::git::get_tree_of_paths;
+    use crate::process::tests::{add_jobs_expect, create_config_ok, prepare_tmp_repo};
+    use anyhow::Error;
+    use git2::Oid;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use std::fs;
+    use std::path::Path;
+
+    fn job_json(id: u32, name: &str, status: &str, commit: &str) -> String {
+        format!(
+            r#"{{"artifacts_expire_at":null,"commit":{{"id":"{commit}"}},"id":{id},"name":"{name}",
+            "ref":"branch1","status":"{status}","web_url":"https://gitlab.localhost/jobs/{id}"}}"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_scan_jobs() {
+        let server = Server::run();
+        let jobs = [
+            job_json(
+                1,
+                "jobB",
+                "success",
+                "26b244f55f8886ccf6a47ea7f24498e9801fc38f",
+            ),
+            job_json(
+                2,
+                "jobA",
+                "failed",
+                "26b244f55f8886ccf6a47ea7f24498e9801fc38f",
+            ),
+            job_json(
+                3,
+                "jobA",
+                "success",
+                "3333333333333333333333333333333333333333",
+            ),
+            job_json(
+                4,
+                "jobA",
+                "success",
+                "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            ),
+            job_json(
+                5,
+                "jobA",
+                "success",
+                "71caf060ef3022468ffd8b4a70e680d7fec78000",
+            ),
+        ];
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/123/jobs"))
+                .times(1..)
+                .respond_with(status_code(200).body(format!("[{}]", jobs.join(",")))),
+        );
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let config = Config {
+            files_to_check: "root-1 root-2 Service-B".to_string(),
+            page_to_fetch_max: 1,
+            commit_to_check_same_ref_max: 10,
+            commit_to_check_same_job_max: 10,
+            ..create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"))
+        };
+        let paths = vec![
+            Path::new("root-1"),
+            Path::new("root-2"),
+            Path::new("Service-B"),
+        ];
+        let head = "26b244f55f8886ccf6a47ea7f24498e9801fc38f";
+        let tree_of_head = get_tree_of_paths(&repo, head, &paths).unwrap();
+        let scanned_jobs = scan_jobs(&config, &repo, &paths, &tree_of_head)
+            .await
+            .unwrap();
+        let job_matches = scanned_jobs
+            .iter()
+            .map(|scanned_job| &scanned_job.job_match)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            job_matches,
+            vec![
+                &DifferentName("jobB".to_string()),
+                &NotSuccessful("failed".to_string()),
+                &CommitMissing,
+                &PathsDiffer(vec!["Service-B".to_string(), "root-2".to_string()]),
+                &PathsDiffer(vec!["root-2".to_string()]),
+            ]
+        );
+        assert_eq!(
+            scanned_jobs[4].to_string(),
+            "job 5 ref=branch1 commit=71caf060 : paths differing : root-2"
+        );
+        assert_eq!(get_closest_candidate(&scanned_jobs).unwrap().job.id, 5);
+        assert_eq!(explain_with_exit_code(Ok(config)).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_explain_api_found() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let server = Server::run();
+        let url = add_jobs_expect(&server);
+        // commit04
+        repo.set_head_detached(Oid::from_str("5e694dadd2979a2680c98c88a2f98df9787947d2").unwrap())
+            .unwrap();
+        let config = create_config_ok(&tmp_dir, &url);
+        let repo = open_repository(&config).unwrap();
+        let paths = vec![Path::new("root-1"), Path::new("Service-A/file-A1")];
+        let tree_of_head =
+            get_tree_of_paths(&repo, "5e694dadd2979a2680c98c88a2f98df9787947d2", &paths).unwrap();
+        let scanned_jobs = scan_jobs(&config, &repo, &paths, &tree_of_head)
+            .await
+            .unwrap();
+        assert_eq!(scanned_jobs.len(), 2);
+        assert_eq!(scanned_jobs[0].job_match, CommitMissing);
+        assert_eq!(scanned_jobs[1].job_match, Match);
+        assert_eq!(explain_with_exit_code(Ok(config.clone())).await, 0);
+        // no side effect
+        assert!(!Path::new(&config.ci_skip_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_explain_api_ancestors() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        repo.set_head_detached(Oid::from_str(COMMIT_04).unwrap())
+            .unwrap();
+        let server = Server::run();
+        add_pipelines_expect(&server, COMMIT_04, "[]");
+        add_pipelines_expect(&server, COMMIT_03, r#"[{"id": 7, "status": "failed"}]"#);
+        add_pipeline_jobs_expect(&server);
+        let config = Config {
+            search: Search::Ancestors,
+            ..create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"))
+        };
+        let paths_to_check = get_paths_to_check(&config).unwrap();
+        let paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
+        let tree_of_head = get_tree_of_paths(&repo, COMMIT_04, &paths).unwrap();
+        let scanned_jobs = scan_jobs(&config, &repo, &paths, &tree_of_head)
+            .await
+            .unwrap();
+        let job_matches = scanned_jobs
+            .iter()
+            .map(|scanned_job| &scanned_job.job_match)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            job_matches,
+            vec![&DifferentName("jobB".to_string()), &Match]
+        );
+        assert_eq!(scanned_jobs[1].job.id, 12345678);
+    }
+
+    #[tokio::test]
+    async fn test_explain_cache() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            ci_job_id: 456,
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        fs::write(&history_path, "abc:1\n").unwrap();
+        assert_eq!(explain_with_exit_code(Ok(config.clone())).await, 1);
+        // no side effect
+        assert_eq!(fs::read_to_string(&history_path).unwrap(), "abc:1\n");
+        assert!(!Path::new(&config.ci_skip_path).exists());
+
+        fs::write(
+            &history_path,
+            "3de26cd90c29f5a3e937bca57e603b1f58f00026:123\n",
+        )
+        .unwrap();
+        assert_eq!(explain_with_exit_code(Ok(config.clone())).await, 0);
+        assert!(!Path::new(&config.ci_skip_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_explain_with_exit_code_ko() {
+        assert_eq!(explain_with_exit_code(Err(Error::msg("error"))).await, 6);
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            backend: Backend::Cache,
+            files_to_check: "".to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        assert_eq!(explain_with_exit_code(Ok(config)).await, 2);
+    }
+}
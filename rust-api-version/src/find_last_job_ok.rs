@@ -1,54 +1,157 @@
+use crate::ancestors::{find_last_job_ok_in_ancestors, Search};
 use crate::config::Config;
 use crate::git::get_tree_of_paths;
-use crate::jobs::{get_project_jobs, GitlabJob};
+use crate::jobs::{GitlabJob, JobPages};
+use crate::trace::SKIP_CI_FINGERPRINT_KEY;
 use crate::verbose;
-use anyhow::Context;
+use anyhow::{bail, Context};
 use git2::Repository;
+use std::fs;
 use std::path::Path;
 
-pub async fn find_last_job_ok(config: &Config) -> anyhow::Result<Option<GitlabJob>> {
+const SKIP_CI_IGNORE_FILE: &str = ".skipciignore";
+
+// the paths of SKIP_IF_TREE_OK_IN_PAST followed by the exclusions of SKIP_CI_EXCLUDES and of the
+// .skipciignore file
+pub fn get_paths_to_check(config: &Config) -> anyhow::Result<Vec<String>> {
+    let mut paths = config
+        .files_to_check
+        .split(' ')
+        .filter(|path| !path.is_empty())
+        .map(String::from)
+        .collect::<Vec<String>>();
+    // the excludes of the config file (SKIP_CI_EXCLUDES)
+    paths.extend(
+        config
+            .excludes
+            .split(' ')
+            .filter(|exclude| !exclude.is_empty())
+            .map(|exclude| format!("!{}", exclude.trim_start_matches('!'))),
+    );
+    let ignore_path = Path::new(&config.project_path).join(SKIP_CI_IGNORE_FILE);
+    if let Ok(content) = fs::read_to_string(&ignore_path) {
+        verbose!("{SKIP_CI_IGNORE_FILE} found");
+        for (line_num, line) in content.lines().enumerate() {
+            // the comments start with "#", at the beginning of the line or after a pattern
+            let line = line.split('#').next().unwrap_or_default();
+            for pattern in line.split_whitespace() {
+                // every pattern of the file is an exclusion, a negation would include a path
+                if pattern.starts_with('!') {
+                    bail!(
+                        "{SKIP_CI_IGNORE_FILE}:{} : the negated pattern {pattern} is not supported",
+                        line_num + 1
+                    );
+                }
+                paths.push(format!("!{pattern}"));
+            }
+        }
+    }
+    Ok(paths)
+}
+
+pub fn open_repository(config: &Config) -> anyhow::Result<Repository> {
     let git_path = Path::new(&config.project_path).join(".git");
-    let repo = Repository::open_bare(git_path.to_str().context("Git Repo path error")?)
-        .context("Git Repo error")?;
+    Repository::open_bare(git_path.to_str().context("Git Repo path error")?)
+        .context("Git Repo error")
+}
+
+// 2. Get the "git ls-tree" of the tree "$SKIP_IF_TREE_OK_IN_PAST" of the current HEAD and its fingerprint
+pub fn get_fingerprint_of_head(
+    config: &Config,
+    repo: &Repository,
+    skip_files_paths: &Vec<&Path>,
+) -> anyhow::Result<String> {
     let head = repo
         .refname_to_id("HEAD")
         .context("Head retrieving error")?;
     verbose!("head = {head}");
-    let skip_files_paths = config
-        .files_to_check
-        .split(' ')
-        .map(Path::new)
-        .collect::<Vec<&Path>>();
+    let tree_of_head = get_tree_of_paths(repo, head.to_string().as_str(), skip_files_paths)?;
+    let fingerprint_of_head = tree_of_head.get_fingerprint(config.fingerprint_hash)?;
+    verbose!("{SKIP_CI_FINGERPRINT_KEY}={fingerprint_of_head}");
+    Ok(fingerprint_of_head)
+}
+
+pub fn get_head_fingerprint(config: &Config) -> anyhow::Result<String> {
+    let repo = open_repository(config)?;
+    let paths_to_check = get_paths_to_check(config)?;
+    let skip_files_paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
+    get_fingerprint_of_head(config, &repo, &skip_files_paths)
+}
+
+// the current job name, then its SKIP_CI_JOB_NAME_ALIASES (a renamed or split job)
+pub fn get_job_names(config: &Config) -> impl Iterator<Item = &String> {
+    std::iter::once(&config.ci_job_name).chain(&config.job_name_aliases)
+}
+
+// the current job name or one of its aliases, compared by SKIP_CI_JOB_NAME_MATCH
+pub fn is_job_name_ok(config: &Config, job_name: &str) -> bool {
+    get_job_names(config).any(|name| config.job_name_match.matches(job_name, name))
+}
 
-    // 2. Get the "git ls-tree" of the tree "$SKIP_IF_TREE_OK_IN_PAST" of the current HEAD
-    let tree_of_head = get_tree_of_paths(&repo, head.to_string().as_str(), &skip_files_paths)?;
+// a successful job of the current job name
+pub fn is_job_ok(config: &Config, job: &GitlabJob) -> bool {
+    is_job_name_ok(config, &job.name) && job.status == "success"
+}
+
+pub async fn find_last_job_ok(config: &Config) -> anyhow::Result<Option<GitlabJob>> {
+    let repo = open_repository(config)?;
+    let paths_to_check = get_paths_to_check(config)?;
+    let skip_files_paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
 
+    let fingerprint_of_head = get_fingerprint_of_head(config, &repo, &skip_files_paths)?;
+    if config.search == Search::Ancestors {
+        return find_last_job_ok_in_ancestors(
+            config,
+            &repo,
+            &skip_files_paths,
+            &fingerprint_of_head,
+        )
+        .await;
+    }
+
+    scan_last_jobs(config, |job| {
+        // 4. Filter jobs : keep current job only
+        if !is_job_ok(config, job) {
+            return false;
+        }
+        // 5. For each job :
+        verbose!("Check job {}", job.id);
+        //     5.1. Get the "git ls-tree" of the tree "$SKIP_IF_TREE_OK_IN_PAST"
+        let tree = get_tree_of_paths(&repo, &job.commit.id, &skip_files_paths);
+        //     5.2. Check if this "git ls-tree" equals the current HEAD "git ls-tree" (see 2.)
+        let fingerprint = tree.and_then(|tree| tree.get_fingerprint(config.fingerprint_hash));
+        match fingerprint {
+            Ok(fingerprint) => fingerprint.eq(&fingerprint_of_head),
+            Err(e) => {
+                verbose!("the job {} doesn't match : {e:#}", job.id);
+                false
+            }
+        }
+    })
+    .await
+}
+
+// 3. scan the last jobs of the project page by page, until is_match returns true for a job or
+// the limits are reached : only the successful jobs of the current job name count in the limits
+pub async fn scan_last_jobs(
+    config: &Config,
+    mut is_match: impl FnMut(&GitlabJob) -> bool,
+) -> anyhow::Result<Option<GitlabJob>> {
     let mut commit_to_check_same_ref = 0;
     let mut commit_to_check_same_job = 0;
 
-    for page_num in 1..=config.page_to_fetch_max {
-        let jobs = get_project_jobs(&config.jobs_api_url, page_num, &config.api_read_token).await?;
-        let job_found = jobs
-            .iter()
-            // 4. Filter jobs : keep current job only
-            .filter(|job| job.name == config.ci_job_name && job.status == "success")
-            // 5. For each job :
-            .find(|job| {
-                verbose!("Check job {}", job.id);
+    let mut pages = JobPages::new(config);
+    while let Some(jobs) = pages.next().await? {
+        let page_num = pages.page_num;
+        let job_found = jobs.into_iter().find(|job| {
+            if is_job_ok(config, job) {
                 commit_to_check_same_job += 1;
-                if let Ok(ci_commit_ref_name) = config.ci_commit_ref_name.clone() {
-                    if job.job_ref.eq(&ci_commit_ref_name) {
-                        commit_to_check_same_ref += 1;
-                    }
-                }
-                //     5.1. Get the "git ls-tree" of the tree "$SKIP_IF_TREE_OK_IN_PAST"
-                let tree = get_tree_of_paths(&repo, &job.commit.id, &skip_files_paths);
-                //     5.2. Check if this "git ls-tree" equals the current HEAD "git ls-tree" (see 2.)
-                match tree {
-                    Ok(tree_content) => tree_content.eq(&tree_of_head),
-                    Err(_) => false,
+                if config.ci_commit_ref_name.as_ref() == Ok(&job.job_ref) {
+                    commit_to_check_same_ref += 1;
                 }
-            });
+            }
+            is_match(job)
+        });
         verbose!(
             "{commit_to_check_same_job} jobs checked, {commit_to_check_same_ref} with the same ref"
         );
@@ -56,7 +159,7 @@ pub async fn find_last_job_ok(config: &Config) -> anyhow::Result<Option<GitlabJo
         match job_found {
             Some(job) => {
                 verbose!("job found in page {page_num} !");
-                return Ok(Some(job.clone()));
+                return Ok(Some(job));
             }
             None => {
                 verbose!("job not found in page {page_num}");
@@ -84,10 +187,13 @@ pub async fn find_last_job_ok(config: &Config) -> anyhow::Result<Option<GitlabJo
 #[cfg(test)]
 mod tests {
     use crate::config::Config;
-    use crate::find_last_job_ok::find_last_job_ok;
+    use crate::find_last_job_ok::{find_last_job_ok, get_paths_to_check};
+    use crate::job_name::JobNameMatch;
     use crate::process::tests::{add_jobs_expect, create_config_ok, prepare_tmp_repo};
     use git2::Oid;
-    use httptest::Server;
+    use httptest::matchers::request;
+    use httptest::responders::status_code;
+    use httptest::{Expectation, Server};
     use std::fs;
     use std::fs::File;
     use std::path::Path;
@@ -108,6 +214,86 @@ mod tests {
         assert_eq!(res.unwrap().unwrap().id, 12345678);
     }
 
+    #[tokio::test]
+    async fn test_find_last_job_ok_parallel() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let server = Server::run();
+        // the commit 03 has the tree of the commit 04
+        let jobs = [
+            (1, "jobB 2/3"),
+            (2, "jobA: [linux, arm64]"),
+            (3, "jobA: [linux, amd64]"),
+            (4, "jobA 1/4"),
+        ]
+        .map(|(id, name)| {
+            format!(
+                r#"{{"artifacts_expire_at": null,
+                    "commit": {{"id": "71caf060ef3022468ffd8b4a70e680d7fec78000"}},
+                    "id": {id}, "name": "{name}", "ref": "branch1", "status": "success",
+                    "web_url": ""}}"#
+            )
+        })
+        .join(",");
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/123/jobs"))
+                .times(1..)
+                .respond_with(status_code(200).body(format!("[{jobs}]"))),
+        );
+
+        // commit04
+        repo.set_head_detached(Oid::from_str("5e694dadd2979a2680c98c88a2f98df9787947d2").unwrap())
+            .unwrap();
+
+        let mut config = create_config_ok(&tmp_dir, &server.url_str("/api/123/jobs"));
+        config.ci_job_name = "jobA 2/3".to_string();
+        assert_eq!(find_last_job_ok(&config).await.unwrap(), None);
+        // another shard, re-sharded from 3 to 4 jobs, not "jobB 2/3"
+        config.job_name_match = JobNameMatch::Parallel;
+        let res = find_last_job_ok(&config).await;
+        assert_eq!(res.unwrap().unwrap().id, 4);
+
+        // the same tuple whatever its spacing, not "jobA: [linux, arm64]"
+        config.ci_job_name = "jobA: [linux,amd64]".to_string();
+        config.job_name_match = JobNameMatch::Matrix;
+        let res = find_last_job_ok(&config).await;
+        assert_eq!(res.unwrap().unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_find_last_job_ok_aliases() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let server = Server::run();
+        let url = add_jobs_expect(&server);
+
+        // commit04
+        repo.set_head_detached(Oid::from_str("5e694dadd2979a2680c98c88a2f98df9787947d2").unwrap())
+            .unwrap();
+
+        // jobA was renamed jobA-unit
+        let mut config = create_config_ok(&tmp_dir, &url);
+        config.ci_job_name = "jobA-unit".to_string();
+        assert_eq!(find_last_job_ok(&config).await.unwrap(), None);
+        config.job_name_aliases = vec!["jobB".to_string(), "jobA".to_string()];
+        let res = find_last_job_ok(&config).await;
+        assert_eq!(res.unwrap().unwrap().id, 12345678);
+    }
+
+    #[tokio::test]
+    async fn test_find_last_job_ok_absent_path() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let server = Server::run();
+        let url = add_jobs_expect(&server);
+
+        // commit04
+        repo.set_head_detached(Oid::from_str("5e694dadd2979a2680c98c88a2f98df9787947d2").unwrap())
+            .unwrap();
+
+        let mut config = create_config_ok(&tmp_dir, &url);
+        config.files_to_check = "root-1 Service-A/file-A1 file-not-found".to_string();
+        let res = find_last_job_ok(&config).await;
+        assert_eq!(res.unwrap().unwrap().id, 12345678);
+    }
+
     #[tokio::test]
     async fn test_find_last_job_ok_git_ko() {
         let tmp_dir = tempdir().unwrap();
@@ -130,9 +316,59 @@ mod tests {
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 2,
             skip: false,
+            ..Default::default()
         };
         let res = find_last_job_ok(&config).await;
         assert_eq!(res.err().map(|e|format!("{e:#}")).unwrap(),
                    format!("Git Repo error: path is not a repository: {}/.git; class=Repository (6); code=NotFound (-3)", tmp_dir.path().to_str().unwrap()));
     }
+
+    #[test]
+    fn test_get_paths_to_check_skipciignore() {
+        let tmp_dir = tempdir().unwrap();
+        fs::write(
+            tmp_dir.path().join(".skipciignore"),
+            "# docs\n**/*.md service-A/docs # generated\n\n**/test-data#data\n",
+        )
+        .unwrap();
+        let mut config = create_config_ok(&tmp_dir, "____");
+        config.files_to_check = "service-A !service-A/tmp".to_string();
+        config.excludes = "**/*.log !**/*.tmp".to_string();
+        assert_eq!(
+            get_paths_to_check(&config).unwrap(),
+            vec![
+                "service-A",
+                "!service-A/tmp",
+                "!**/*.log",
+                "!**/*.tmp",
+                "!**/*.md",
+                "!service-A/docs",
+                "!**/test-data"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_paths_to_check_without_skipciignore() {
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config_ok(&tmp_dir, "____");
+        assert_eq!(
+            get_paths_to_check(&config).unwrap(),
+            vec!["root-1", "Service-A/file-A1"]
+        );
+    }
+
+    #[test]
+    fn test_get_paths_to_check_skipciignore_negation() {
+        let tmp_dir = tempdir().unwrap();
+        fs::write(tmp_dir.path().join(".skipciignore"), "**/*.md\n!keep.md\n").unwrap();
+        let config = create_config_ok(&tmp_dir, "____");
+        assert_eq!(
+            get_paths_to_check(&config)
+                .err()
+                .map(|e| format!("{e:#}"))
+                .unwrap(),
+            ".skipciignore:2 : the negated pattern !keep.md is not supported"
+        );
+    }
 }
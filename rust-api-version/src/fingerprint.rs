@@ -0,0 +1,187 @@
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::find_last_job_ok::{get_paths_to_check, open_repository};
+use crate::git::{get_tree_of_paths, FingerprintHash};
+use crate::skipci_log::red;
+use anyhow::Context;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FingerprintEntry {
+    // the git filemode in octal, "000000" if the path is absent
+    pub filemode: String,
+    pub id: String,
+    pub path: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FingerprintReport {
+    pub commit: String,
+    pub hash: String,
+    pub entries: Vec<FingerprintEntry>,
+    pub fingerprint: String,
+}
+
+impl FingerprintReport {
+    // the "<filemode> <id> <path>" lines of the entries, then the fingerprint
+    fn to_text(&self) -> String {
+        let mut text = format!("commit {}\n", self.commit);
+        for entry in &self.entries {
+            text += &format!("{} {} {}\n", entry.filemode, entry.id, entry.path);
+        }
+        text += &format!("{} {}", self.hash, self.fingerprint);
+        text
+    }
+}
+
+// the tree of the paths to check in the commit-ish (HEAD by default) and its fingerprint
+fn get_fingerprint_report(
+    config: &Config,
+    commit: Option<&str>,
+) -> anyhow::Result<FingerprintReport> {
+    let repo = open_repository(config)?;
+    let commit = commit.unwrap_or("HEAD");
+    let commit_id = repo
+        .revparse_single(commit)
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| format!("commit {commit} not found"))?
+        .id()
+        .to_string();
+    let paths_to_check = get_paths_to_check(config)?;
+    let paths = paths_to_check.iter().map(Path::new).collect::<Vec<&Path>>();
+    let tree = get_tree_of_paths(&repo, &commit_id, &paths)?;
+    Ok(FingerprintReport {
+        hash: match config.fingerprint_hash {
+            FingerprintHash::Sha1 => "sha1",
+            FingerprintHash::Sha256 => "sha256",
+        }
+        .to_string(),
+        fingerprint: tree.get_fingerprint(config.fingerprint_hash)?,
+        entries: tree
+            .entries
+            .iter()
+            .map(|entry| FingerprintEntry {
+                filemode: format!("{:06o}", entry.filemode),
+                id: entry.id.to_string(),
+                path: entry.path.clone(),
+            })
+            .collect(),
+        commit: commit_id,
+    })
+}
+
+// print the tree of the paths and the fingerprint of a commit, for the local debugging : two
+// commits with the same fingerprint are equivalent
+pub fn fingerprint_with_exit_code(
+    config_result: anyhow::Result<Config>,
+    commit: Option<&str>,
+    format: OutputFormat,
+) -> i32 {
+    match config_result {
+        Ok(config) => {
+            let output = get_fingerprint_report(&config, commit).and_then(|report| match format {
+                OutputFormat::Text => Ok(report.to_text()),
+                OutputFormat::Json => {
+                    serde_json::to_string_pretty(&report).context("serialize fingerprint error")
+                }
+            });
+            match output {
+                Ok(output) => {
+                    println!("{output}");
+                    0
+                }
+                Err(e) => {
+                    red(&format!("❌ FINGERPRINT ERROR : \n{e:#?}"));
+                    2
+                }
+            }
+        }
+        Err(e) => {
+            red(&format!("❌ CONFIG ERROR : \n{e:#?}"));
+            6
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::OutputFormat;
+    use crate::config::Config;
+    use crate::fingerprint::{fingerprint_with_exit_code, get_fingerprint_report};
+    use crate::git::FingerprintHash;
+    use crate::process::tests::{create_config_ok, prepare_tmp_repo};
+    use anyhow::Error;
+
+    #[test]
+    fn test_get_fingerprint_report() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            files_to_check: "root-1 Service-A/file-A1 not-found".to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        let report = get_fingerprint_report(&config, None).unwrap();
+        assert_eq!(report.commit, "26b244f55f8886ccf6a47ea7f24498e9801fc38f");
+        assert_eq!(
+            report.fingerprint,
+            "3de26cd90c29f5a3e937bca57e603b1f58f00026"
+        );
+        let text = report.to_text();
+        assert!(text.starts_with("commit 26b244f55f8886ccf6a47ea7f24498e9801fc38f\n100644 "));
+        assert!(text.contains(
+            "\n000000 0000000000000000000000000000000000000000 not-found\n\
+            sha1 3de26cd90c29f5a3e937bca57e603b1f58f00026"
+        ));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["entries"][2]["path"], "not-found");
+        assert_eq!(json["hash"], "sha1");
+
+        // commit-01 and commit-07 have the same Service-A and root-1
+        let report_01 = get_fingerprint_report(&config, Some("HEAD~6")).unwrap();
+        assert_eq!(report_01.commit, "ef08d93fdeabf23734248d6f95ab4ff3952e9856");
+        assert_eq!(report_01.fingerprint, report.fingerprint);
+        let report_01 = get_fingerprint_report(&config, Some("ef08d93")).unwrap();
+        assert_eq!(report_01.fingerprint, report.fingerprint);
+
+        let config = Config {
+            fingerprint_hash: FingerprintHash::Sha256,
+            files_to_check: "root-2".to_string(),
+            ..config
+        };
+        let report_07 = get_fingerprint_report(&config, None).unwrap();
+        let report_01 = get_fingerprint_report(&config, Some("HEAD~6")).unwrap();
+        assert_eq!(report_07.hash, "sha256");
+        assert_eq!(report_07.fingerprint.len(), 64);
+        assert_ne!(report_07.fingerprint, report_01.fingerprint);
+    }
+
+    #[test]
+    fn test_fingerprint_with_exit_code() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = create_config_ok(&tmp_dir, "____");
+        assert_eq!(
+            fingerprint_with_exit_code(Ok(config.clone()), None, OutputFormat::Text),
+            0
+        );
+        assert_eq!(
+            fingerprint_with_exit_code(Ok(config.clone()), Some("HEAD~1"), OutputFormat::Json),
+            0
+        );
+        assert_eq!(
+            fingerprint_with_exit_code(Ok(config.clone()), Some("unknown"), OutputFormat::Text),
+            2
+        );
+        let config = Config {
+            files_to_check: "".to_string(),
+            ..config
+        };
+        assert_eq!(
+            fingerprint_with_exit_code(Ok(config), None, OutputFormat::Text),
+            2
+        );
+        assert_eq!(
+            fingerprint_with_exit_code(Err(Error::msg("error")), None, OutputFormat::Text),
+            6
+        );
+    }
+}
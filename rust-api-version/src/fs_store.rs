@@ -0,0 +1,288 @@
+use crate::config::Config;
+use crate::find_last_job_ok::get_job_names;
+use crate::history::HistoryRecord;
+use crate::verbose;
+use anyhow::Context;
+use std::fs;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const FS_STORE_LOCK_FILE: &str = ".lock";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// the directory name of the job : the characters other than [A-Za-z0-9_-] are percent-encoded
+pub fn encode_job_name(job_name: &str) -> String {
+    job_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+// <SKIP_CI_FS_ROOT>/<CI_PROJECT_ID>/<job name>
+fn get_job_dir(config: &Config, job_name: &str) -> PathBuf {
+    Path::new(&config.fs_root)
+        .join(&config.ci_project_id)
+        .join(encode_job_name(job_name))
+}
+
+fn is_stale(path: &Path, max_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > max_age)
+}
+
+fn get_max_age(config: &Config) -> Duration {
+    Duration::from_secs(config.fs_max_age_days.saturating_mul(SECONDS_PER_DAY))
+}
+
+// the lock file of the job directory, read-only : shared to read
+fn open_lock_file_to_read(job_dir: &Path) -> anyhow::Result<Option<File>> {
+    match File::open(job_dir.join(FS_STORE_LOCK_FILE)) {
+        Ok(lock) => Ok(Some(lock)),
+        // no entry written by this version yet
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("open {FS_STORE_LOCK_FILE} of {job_dir:?} error")),
+    }
+}
+
+// the lock file of the job directory, created if missing : exclusive to write and prune
+fn open_lock_file(job_dir: &Path) -> anyhow::Result<File> {
+    File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(job_dir.join(FS_STORE_LOCK_FILE))
+        .with_context(|| format!("open {FS_STORE_LOCK_FILE} of {job_dir:?} error"))
+}
+
+// find <fingerprint>.json in the directory of the job, then in the directories of its aliases
+pub fn find_in_fs_store(
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    for job_name in get_job_names(config) {
+        let record = find_in_job_dir(config, &get_job_dir(config, job_name), fingerprint)?;
+        if record.is_some() {
+            return Ok(record);
+        }
+    }
+    Ok(None)
+}
+
+// find <fingerprint>.json in a job directory, the stale entries are ignored
+fn find_in_job_dir(
+    config: &Config,
+    job_dir: &Path,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    let entry_path = job_dir.join(format!("{fingerprint}.json"));
+    if !entry_path.exists() {
+        verbose!("{entry_path:?} doesn't exists");
+        return Ok(None);
+    }
+    if is_stale(&entry_path, get_max_age(config)) {
+        verbose!("{entry_path:?} is stale");
+        return Ok(None);
+    }
+    let lock = open_lock_file_to_read(job_dir)?;
+    if let Some(lock) = &lock {
+        lock.lock_shared().context("fs store lock error")?;
+    }
+    let content = match fs::read_to_string(&entry_path) {
+        Ok(content) => content,
+        // pruned in the meantime
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("read {entry_path:?} error")),
+    };
+    if let Some(lock) = &lock {
+        lock.unlock().context("fs store unlock error")?;
+    }
+    let record = serde_json::from_str::<HistoryRecord>(&content)
+        .with_context(|| format!("parse {entry_path:?} error"))?;
+    verbose!("record found in {entry_path:?} : {record}");
+    Ok(Some(record))
+}
+
+// write <fingerprint>.json in the job directory and prune the stale entries
+pub fn add_to_fs_store(config: &Config, record: &HistoryRecord) -> anyhow::Result<()> {
+    let job_dir = get_job_dir(config, &config.ci_job_name);
+    fs::create_dir_all(&job_dir).with_context(|| format!("create {job_dir:?} error"))?;
+    let lock = open_lock_file(&job_dir)?;
+    lock.lock().context("fs store lock error")?;
+
+    let entry_path = job_dir.join(format!("{}.json", record.fingerprint));
+    // write then rename : the readers never see a partial entry
+    let tmp_path = job_dir.join(format!("{}.json.{}.tmp", record.fingerprint, record.job_id));
+    let content = serde_json::to_string(record).context("serialize record error")?;
+    fs::write(&tmp_path, content).with_context(|| format!("write {tmp_path:?} error"))?;
+    fs::rename(&tmp_path, &entry_path).with_context(|| format!("write {entry_path:?} error"))?;
+    verbose!("{record} written in {entry_path:?}");
+
+    prune_fs_store(&job_dir, get_max_age(config))?;
+    lock.unlock().context("fs store unlock error")?;
+    Ok(())
+}
+
+// remove the entries older than the max age
+fn prune_fs_store(job_dir: &Path, max_age: Duration) -> anyhow::Result<()> {
+    let entries = fs::read_dir(job_dir).with_context(|| format!("read {job_dir:?} error"))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_entry = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name != FS_STORE_LOCK_FILE);
+        if is_entry && is_stale(&path, max_age) {
+            verbose!("prune {path:?}");
+            if let Err(e) = fs::remove_file(&path) {
+                verbose!("prune {path:?} error : {e}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::fs_store::{add_to_fs_store, encode_job_name, find_in_fs_store};
+    use crate::history::tests::record;
+    use crate::process::tests::create_config_ok;
+    use std::fs;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use tempfile::{tempdir, TempDir};
+
+    fn create_config(root_dir: &TempDir) -> Config {
+        Config {
+            backend: Backend::Fs,
+            fs_root: root_dir.path().to_str().unwrap().to_string(),
+            ci_project_id: "123".to_string(),
+            ci_job_name: "test 1/3".to_string(),
+            fs_max_age_days: 30,
+            ..create_config_ok(root_dir, "____")
+        }
+    }
+
+    #[test]
+    fn test_encode_job_name() {
+        assert_eq!(encode_job_name("jobA"), "jobA");
+        assert_eq!(encode_job_name("test 1/3"), "test%201%2F3");
+        assert_eq!(encode_job_name(".."), "%2E%2E");
+        assert_eq!(encode_job_name("build: [é]"), "build%3A%20%5B%C3%A9%5D");
+    }
+
+    #[test]
+    fn test_fs_store() {
+        let root_dir = tempdir().unwrap();
+        let config = create_config(&root_dir);
+        assert_eq!(find_in_fs_store(&config, "abc").unwrap(), None);
+
+        add_to_fs_store(&config, &record("abc", 1)).unwrap();
+        add_to_fs_store(&config, &record("abc", 2)).unwrap();
+        add_to_fs_store(&config, &record("def", 3)).unwrap();
+
+        let job_dir = root_dir.path().join("123").join("test%201%2F3");
+        assert!(job_dir.join("abc.json").exists());
+        assert_eq!(
+            fs::read_to_string(job_dir.join("abc.json")).unwrap(),
+            r#"{"fingerprint":"abc","job_id":2,"job_ref":"main","timestamp":1700000000}"#
+        );
+        assert_eq!(
+            find_in_fs_store(&config, "abc").unwrap(),
+            Some(record("abc", 2))
+        );
+        assert_eq!(find_in_fs_store(&config, "ab").unwrap(), None);
+
+        // another job
+        let config = Config {
+            ci_job_name: "test 2/3".to_string(),
+            ..config
+        };
+        assert_eq!(find_in_fs_store(&config, "abc").unwrap(), None);
+
+        // the records of the aliases
+        let config = Config {
+            job_name_aliases: vec!["test 1/3".to_string()],
+            ..config
+        };
+        assert_eq!(
+            find_in_fs_store(&config, "abc").unwrap(),
+            Some(record("abc", 2))
+        );
+    }
+
+    #[test]
+    fn test_fs_store_stale() {
+        let root_dir = tempdir().unwrap();
+        let config = create_config(&root_dir);
+        add_to_fs_store(&config, &record("abc", 1)).unwrap();
+        let job_dir = root_dir.path().join("123").join("test%201%2F3");
+        let old = SystemTime::now() - Duration::from_secs(31 * 24 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(job_dir.join("abc.json"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_eq!(find_in_fs_store(&config, "abc").unwrap(), None);
+
+        // pruned by the next record
+        add_to_fs_store(&config, &record("def", 2)).unwrap();
+        assert!(!job_dir.join("abc.json").exists());
+        assert!(job_dir.join("def.json").exists());
+        assert!(job_dir.join(".lock").exists());
+    }
+
+    #[test]
+    fn test_fs_store_lookup_read_only() {
+        let root_dir = tempdir().unwrap();
+        let config = Config {
+            fs_max_age_days: u64::MAX,
+            ..create_config(&root_dir)
+        };
+        let job_dir = root_dir.path().join("123").join("test%201%2F3");
+        fs::create_dir_all(&job_dir).unwrap();
+        fs::write(
+            job_dir.join("abc.json"),
+            r#"{"fingerprint":"abc","job_id":1,"job_ref":"main","timestamp":1700000000}"#,
+        )
+        .unwrap();
+        // the lookup doesn't create the lock file
+        assert_eq!(
+            find_in_fs_store(&config, "abc").unwrap(),
+            Some(record("abc", 1))
+        );
+        assert!(!job_dir.join(".lock").exists());
+    }
+
+    #[test]
+    fn test_fs_store_ko() {
+        let root_dir = tempdir().unwrap();
+        let config = create_config(&root_dir);
+        let job_dir = root_dir.path().join("123").join("test%201%2F3");
+        fs::create_dir_all(&job_dir).unwrap();
+        fs::write(job_dir.join("abc.json"), "{").unwrap();
+        assert!(find_in_fs_store(&config, "abc").is_err());
+
+        let config = Config {
+            fs_root: root_dir
+                .path()
+                .join("123")
+                .join("test%201%2F3")
+                .join("abc.json")
+                .to_str()
+                .unwrap()
+                .to_string(),
+            ..config
+        };
+        assert!(add_to_fs_store(&config, &record("abc", 1)).is_err());
+    }
+}
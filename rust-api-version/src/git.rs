@@ -1,16 +1,149 @@
+use crate::glob::{glob_match, glob_match_dir_prefix, is_glob};
 use crate::verbose;
 use anyhow::{anyhow, Context};
-use git2::{Oid, Repository};
+use git2::{
+    DiffOptions, DiffStatsFormat, ErrorCode, ObjectType, Oid, Repository, Tree, TreeWalkMode,
+    TreeWalkResult,
+};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
 use std::path::Path;
 use std::string::String;
 
+const TREE_FILEMODE: i32 = 0o040000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FingerprintHash {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeOfPathsEntry {
+    // the git filemode of the entry, 0 if the path is absent
+    pub filemode: i32,
+    // the id of the entry, Oid::zero() if the path is absent
+    pub id: Oid,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TreeOfPaths {
+    pub entries: Vec<TreeOfPathsEntry>,
+}
+
+impl Display for TreeOfPaths {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{} {}", entry.id, entry.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl TreeOfPaths {
+    fn push(&mut self, filemode: i32, id: Oid, path: &str) {
+        self.entries.push(TreeOfPathsEntry {
+            filemode,
+            id,
+            path: path.to_string(),
+        });
+    }
+
+    // the content of the tree object created by the bash and go cache versions :
+    // `git ls-tree HEAD -- $SKIP_IF_TREE_OK_IN_PAST | tr / '|' | git mktree`
+    // the absent paths are not in the tree : adding a path changes the fingerprint
+    fn get_mktree_content(&self) -> Vec<u8> {
+        let mut entries = self
+            .entries
+            .iter()
+            .filter(|entry| !entry.id.is_zero())
+            .map(|entry| (entry.filemode, entry.path.replace('/', "|"), entry.id))
+            .collect::<Vec<(i32, String, Oid)>>();
+        entries.sort_by(|(mode_a, name_a, _), (mode_b, name_b, _)| {
+            compare_tree_entry_names(name_a, *mode_a, name_b, *mode_b)
+        });
+        entries.dedup_by(|(_, name_a, _), (_, name_b, _)| name_a == name_b);
+        let mut content = Vec::new();
+        for (filemode, name, id) in entries {
+            content.extend_from_slice(format!("{filemode:o} {name}\0").as_bytes());
+            content.extend_from_slice(id.as_bytes());
+        }
+        content
+    }
+
+    // the paths whose entries differ from the entries of the other tree, the paths absent from
+    // one of the trees included
+    pub fn get_differing_paths(&self, other: &TreeOfPaths) -> Vec<String> {
+        let entries = |tree: &TreeOfPaths| {
+            tree.entries
+                .iter()
+                .map(|entry| (entry.path.clone(), (entry.filemode, entry.id)))
+                .collect::<BTreeMap<String, (i32, Oid)>>()
+        };
+        let (entries, other_entries) = (entries(self), entries(other));
+        entries
+            .keys()
+            .chain(other_entries.keys())
+            .filter(|path| entries.get(*path) != other_entries.get(*path))
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    // the SHA-1 of the "git mktree" tree (= the tree id), or the SHA-256 of the same tree object
+    pub fn get_fingerprint(&self, hash: FingerprintHash) -> anyhow::Result<String> {
+        let content = self.get_mktree_content();
+        let fingerprint = match hash {
+            FingerprintHash::Sha1 => Oid::hash_object(ObjectType::Tree, &content)
+                .context("hash tree error")?
+                .to_string(),
+            FingerprintHash::Sha256 => {
+                let mut object = format!("tree {}\0", content.len()).into_bytes();
+                object.extend_from_slice(&content);
+                let digest = ring::digest::digest(&ring::digest::SHA256, &object);
+                digest
+                    .as_ref()
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect::<String>()
+            }
+        };
+        Ok(fingerprint)
+    }
+}
+
+// git sorts the tree entries by name, the tree names are compared as if they end with a "/"
+fn compare_tree_entry_names(name_a: &str, mode_a: i32, name_b: &str, mode_b: i32) -> Ordering {
+    let key = |name: &str, mode: i32| {
+        let mut key = name.as_bytes().to_vec();
+        if mode == TREE_FILEMODE {
+            key.push(b'/');
+        }
+        key
+    };
+    key(name_a, mode_a).cmp(&key(name_b, mode_b))
+}
+
 pub fn get_tree_of_paths(
     repo: &Repository,
     commit_hash: &str,
     paths: &Vec<&Path>,
-) -> anyhow::Result<String> {
+) -> anyhow::Result<TreeOfPaths> {
     verbose!("get_tree_of_paths of {commit_hash} : {paths:?}");
-    if paths.is_empty() {
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+    for path in paths {
+        let path_str = path.to_str().context("path empty")?;
+        match path_str.strip_prefix('!') {
+            Some(exclude) => excludes.push(exclude),
+            None => includes.push(path_str),
+        }
+    }
+    if includes.is_empty() {
         return Err(anyhow!("paths is empty".to_string()));
     }
 
@@ -24,26 +157,202 @@ pub fn get_tree_of_paths(
         .tree()
         .with_context(|| format!("tree of commit {commit_hash} not found error"))?;
 
-    let mut tree_of_job_files = String::new();
-    for path in paths {
-        let tree_id = tree
-            .get_path(path)
-            .with_context(|| format!("tree of commit {commit_hash} not found error"))?
-            .id();
-        let path_str = path.to_str().context("path empty")?;
-        tree_of_job_files.push_str(&format!("{tree_id} {path_str}\n"));
+    let mut tree_of_job_files = TreeOfPaths::default();
+    for path_str in &includes {
+        let path_str = *path_str;
+        let entries = if is_glob(path_str) {
+            expand_glob(&tree, path_str)?
+        } else if let Some(dir) = path_str.strip_suffix('/') {
+            // "dir/" : the entries of the directory, like "git ls-tree HEAD -- dir/"
+            get_dir_entries(repo, &tree, dir.trim_end_matches('/'))
+                .with_context(|| format!("tree of commit {commit_hash} not found error"))?
+        } else if includes.contains(&format!("{path_str}/").as_str()) {
+            // "dir" and "dir/" : ls-tree lists the entries of the directory only
+            continue;
+        } else {
+            get_path_entry(&tree, path_str)
+                .with_context(|| format!("tree of commit {commit_hash} not found error"))?
+                .into_iter()
+                .collect()
+        };
+        if entries.is_empty() {
+            // the absent paths are part of the fingerprint : adding a path changes it
+            verbose!("{path_str} is absent from the commit {commit_hash}");
+            tree_of_job_files.push(0, Oid::zero(), path_str);
+        }
+        for (filemode, tree_id, entry_path) in entries {
+            push_filtered_entries(
+                repo,
+                &mut tree_of_job_files,
+                filemode,
+                tree_id,
+                &entry_path,
+                &excludes,
+            )?;
+        }
     }
 
     verbose!("{}\n{tree_of_job_files}{}", "-".repeat(80), "-".repeat(80));
     Ok(tree_of_job_files)
 }
 
+// the entry of the path, None if the path is absent
+fn get_path_entry(tree: &Tree, path: &str) -> Result<Option<(i32, Oid, String)>, git2::Error> {
+    match tree.get_path(Path::new(path)) {
+        Ok(entry) => Ok(Some((entry.filemode(), entry.id(), path.to_string()))),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// the entries of the directory, none if the path is absent or is not a directory
+fn get_dir_entries(
+    repo: &Repository,
+    tree: &Tree,
+    dir: &str,
+) -> Result<Vec<(i32, Oid, String)>, git2::Error> {
+    match get_path_entry(tree, dir)? {
+        Some((TREE_FILEMODE, tree_id, _)) => Ok(repo
+            .find_tree(tree_id)?
+            .iter()
+            .map(|entry| {
+                let name = entry.name().unwrap_or_default();
+                (entry.filemode(), entry.id(), format!("{dir}/{name}"))
+            })
+            .collect()),
+        _ => Ok(vec![]),
+    }
+}
+
+fn get_tree_of_commit<'a>(repo: &'a Repository, commit_hash: &str) -> anyhow::Result<Tree<'a>> {
+    let commit_oid = Oid::from_str(commit_hash).context("commit hash error")?;
+    repo.find_commit(commit_oid)
+        .and_then(|commit| commit.tree())
+        .with_context(|| format!("tree of commit {commit_hash} not found error"))
+}
+
+// the "git diff --stat <from> <to> -- <paths>" output without the files of the excluded paths
+pub fn get_diff_stat(
+    repo: &Repository,
+    from_commit_hash: &str,
+    to_commit_hash: &str,
+    paths: &Vec<&Path>,
+) -> anyhow::Result<String> {
+    let from_tree = get_tree_of_commit(repo, from_commit_hash)?;
+    let to_tree = get_tree_of_commit(repo, to_commit_hash)?;
+    let mut includes = DiffOptions::new();
+    let mut excludes = Vec::new();
+    for path in paths {
+        let path_str = path.to_str().context("path empty")?;
+        match path_str.strip_prefix('!') {
+            Some(exclude) => excludes.push(exclude),
+            None => {
+                includes.pathspec(path_str);
+            }
+        }
+    }
+    let diff = repo
+        .diff_tree_to_tree(Some(&from_tree), Some(&to_tree), Some(&mut includes))
+        .context("diff error")?;
+    // the files of the diff that are not excluded, with the matcher of get_tree_of_paths
+    let mut files = DiffOptions::new();
+    files.disable_pathspec_match(true);
+    let mut files_count = 0;
+    for delta in diff.deltas() {
+        let file = delta.new_file().path().or(delta.old_file().path());
+        let file = file.and_then(Path::to_str).context("diff path error")?;
+        if !is_excluded(&excludes, file) {
+            files.pathspec(file);
+            files_count += 1;
+        }
+    }
+    if files_count == 0 {
+        return Ok(String::new());
+    }
+    let stats = repo
+        .diff_tree_to_tree(Some(&from_tree), Some(&to_tree), Some(&mut files))
+        .and_then(|diff| diff.stats())
+        .context("diff error")?;
+    let buf = stats
+        .to_buf(DiffStatsFormat::FULL, 80)
+        .context("diff stats error")?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
+
+// list the entries of the tree that match the glob pattern, the matched folders are not walked
+fn expand_glob(tree: &Tree, pattern: &str) -> anyhow::Result<Vec<(i32, Oid, String)>> {
+    let mut entries = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let entry_path = format!("{root}{}", entry.name().unwrap_or_default());
+        if glob_match(pattern, &entry_path) {
+            entries.push((entry.filemode(), entry.id(), entry_path));
+            TreeWalkResult::Skip
+        } else if entry.kind() == Some(ObjectType::Tree)
+            && !glob_match_dir_prefix(pattern, &entry_path)
+        {
+            TreeWalkResult::Skip
+        } else {
+            TreeWalkResult::Ok
+        }
+    })
+    .with_context(|| format!("walk tree error for the pattern '{pattern}'"))?;
+    Ok(entries)
+}
+
+fn is_excluded(excludes: &[&str], path: &str) -> bool {
+    excludes.iter().any(|exclude| glob_match(exclude, path))
+}
+
+fn may_contain_excluded(excludes: &[&str], dir_path: &str) -> bool {
+    excludes
+        .iter()
+        .any(|exclude| glob_match_dir_prefix(exclude, dir_path))
+}
+
+// add the entry, or its not excluded sub-entries if an exclusion pattern could match inside it :
+// the excluded files don't change the fingerprint
+fn push_filtered_entries(
+    repo: &Repository,
+    tree_of_job_files: &mut TreeOfPaths,
+    filemode: i32,
+    tree_id: Oid,
+    entry_path: &str,
+    excludes: &[&str],
+) -> anyhow::Result<()> {
+    if is_excluded(excludes, entry_path) {
+        verbose!("{entry_path} is excluded");
+    } else if filemode == TREE_FILEMODE && may_contain_excluded(excludes, entry_path) {
+        let sub_tree = repo
+            .find_tree(tree_id)
+            .with_context(|| format!("tree {tree_id} of {entry_path} not found error"))?;
+        sub_tree
+            .walk(TreeWalkMode::PreOrder, |root, entry| {
+                let path = format!("{entry_path}/{root}{}", entry.name().unwrap_or_default());
+                if is_excluded(excludes, &path) {
+                    TreeWalkResult::Skip
+                } else if entry.kind() == Some(ObjectType::Tree)
+                    && may_contain_excluded(excludes, &path)
+                {
+                    TreeWalkResult::Ok
+                } else {
+                    tree_of_job_files.push(entry.filemode(), entry.id(), &path);
+                    TreeWalkResult::Skip
+                }
+            })
+            .with_context(|| format!("walk tree error of {entry_path}"))?;
+    } else {
+        tree_of_job_files.push(filemode, tree_id, entry_path);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::git::get_tree_of_paths;
+    use crate::git::{get_diff_stat, get_tree_of_paths, FingerprintHash};
     use git2::Repository;
     use std::fs::File;
     use std::path::Path;
+    use std::process::Command;
     use tempfile::{tempdir, TempDir};
 
     fn get_tmp_repo() -> (TempDir, Repository) {
@@ -65,7 +374,7 @@ mod tests {
 
         let tree_of_paths =
             get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths).unwrap();
-        assert_eq!(tree_of_paths,"d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\nd00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-A/file-A1\n");
+        assert_eq!(tree_of_paths.to_string(),"d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\nd00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-A/file-A1\n");
     }
 
     #[test]
@@ -78,7 +387,7 @@ mod tests {
         )
         .unwrap();
         assert_eq!(
-            tree_of_paths,
+            tree_of_paths.to_string(),
             "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\n"
         );
     }
@@ -120,7 +429,7 @@ mod tests {
     }
 
     #[test]
-    fn test_get_tree_of_paths_not_found_error() {
+    fn test_get_tree_of_paths_not_found() {
         let (_tmp_dir, repo) = get_tmp_repo();
         let paths = "root-1 Service-A/file-A1 file-not-found"
             .split(' ')
@@ -128,10 +437,283 @@ mod tests {
             .collect::<Vec<&Path>>();
         let tree_of_paths =
             get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths);
-        let mut err_msg = String::new();
-        err_msg
-            .push_str("tree of commit ef08d93fdeabf23734248d6f95ab4ff3952e9856 not found error: ");
-        err_msg.push_str("the path 'file-not-found' does not exist in the given tree; class=Tree (14); code=NotFound (-3)");
-        assert_eq!(tree_of_paths.err().map(|e| format!("{e:#}")), Some(err_msg));
+        assert_eq!(
+            tree_of_paths.unwrap().to_string(),
+            "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\n\
+             d00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-A/file-A1\n\
+             0000000000000000000000000000000000000000 file-not-found\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_glob() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = vec![Path::new("Service-*/file-?1"), Path::new("root-1")];
+        let tree_of_paths =
+            get_tree_of_paths(&repo, "26b244f55f8886ccf6a47ea7f24498e9801fc38f", &paths).unwrap();
+        assert_eq!(
+            tree_of_paths.to_string(),
+            "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-A/file-A1\n\
+             00750edc07d6415dcc07ae0351e9397b0222b7ba Service-B/file-B1\n\
+             d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_glob_folder_and_double_star() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let tree_of_paths = get_tree_of_paths(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            &vec![Path::new("Service-[A]"), Path::new("**/file-B2")],
+        )
+        .unwrap();
+        assert_eq!(
+            tree_of_paths.to_string(),
+            "2bd7c857eb491a42e7638cdb7d0f421604359233 Service-A\n\
+             d00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-B/file-B2\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_glob_no_match() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let tree_of_paths = get_tree_of_paths(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            &vec![Path::new("Service-C/*")],
+        );
+        assert_eq!(
+            tree_of_paths.unwrap().to_string(),
+            "0000000000000000000000000000000000000000 Service-C/*\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_exclusions() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = "Service-A Service-B !Service-B/file-B2 !**/*-A2"
+            .split(' ')
+            .map(Path::new)
+            .collect::<Vec<&Path>>();
+        let tree_of_paths =
+            get_tree_of_paths(&repo, "26b244f55f8886ccf6a47ea7f24498e9801fc38f", &paths).unwrap();
+        assert_eq!(
+            tree_of_paths.to_string(),
+            "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d Service-A/file-A1\n\
+             00750edc07d6415dcc07ae0351e9397b0222b7ba Service-B/file-B1\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_exclusions_same_fingerprint() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        // root-2 changes in each commit
+        let paths = vec![Path::new("root-*"), Path::new("!root-2")];
+        let commit_01 =
+            get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths);
+        let commit_07 =
+            get_tree_of_paths(&repo, "26b244f55f8886ccf6a47ea7f24498e9801fc38f", &paths);
+        assert_eq!(
+            commit_01.unwrap().to_string(),
+            "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\n"
+        );
+        assert_eq!(
+            commit_07.unwrap().to_string(),
+            "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d root-1\n"
+        );
+    }
+
+    #[test]
+    fn test_get_tree_of_paths_exclusions_only() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let tree_of_paths = get_tree_of_paths(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            &vec![Path::new("!root-1")],
+        );
+        assert_eq!(
+            tree_of_paths.err().map(|e| format!("{:#}", e)).unwrap(),
+            "paths is empty"
+        );
+    }
+
+    // the fingerprint of the bash version : git ls-tree <commit> -- <paths> | tr / '|' | git mktree
+    fn git_mktree(repo: &Repository, commit: &str, paths: &str) -> String {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "git ls-tree {commit} -- {paths} | tr / '|' | git mktree"
+            ))
+            .env("GIT_DIR", repo.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    #[test]
+    fn test_get_fingerprint_same_as_git_mktree() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let commit = "ef08d93fdeabf23734248d6f95ab4ff3952e9856";
+        // the paths of the rust version, the same paths for git ls-tree
+        for (paths, git_paths) in [
+            ("Service-A/ root-1", "Service-A/ root-1"),
+            ("Service-A root-1", "Service-A root-1"),
+            (
+                "Service-A Service-A/ root-1/",
+                "Service-A Service-A/ root-1/",
+            ),
+            ("Service-* root-1", "Service-A Service-B root-1"),
+            ("Service-B/ !Service-B/file-B2", "Service-B/file-B1"),
+            ("Service-A !Service-A/file-A1", "Service-A/file-A2"),
+        ] {
+            let paths = paths.split(' ').map(Path::new).collect::<Vec<&Path>>();
+            let fingerprint = get_tree_of_paths(&repo, commit, &paths)
+                .unwrap()
+                .get_fingerprint(FingerprintHash::Sha1)
+                .unwrap();
+            assert_eq!(
+                fingerprint,
+                git_mktree(&repo, commit, git_paths),
+                "{paths:?}"
+            );
+        }
+        assert_eq!(
+            git_mktree(&repo, commit, "Service-A/ root-1"),
+            "cecb892d043f6864e4b22288d04f48201d0a112e"
+        );
+    }
+
+    // git ls-tree ef08d93 -- root-1 Service-A/file-A1 | tr / '|' | git mktree
+    #[test]
+    fn test_get_fingerprint_mktree_sha1() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = vec![Path::new("Service-A/file-A1"), Path::new("root-1")];
+        let tree_of_paths =
+            get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths).unwrap();
+        assert_eq!(
+            tree_of_paths
+                .get_fingerprint(FingerprintHash::Sha1)
+                .unwrap(),
+            "3de26cd90c29f5a3e937bca57e603b1f58f00026"
+        );
+        assert_eq!(
+            tree_of_paths
+                .get_fingerprint(FingerprintHash::Sha256)
+                .unwrap(),
+            "ec7e024e01da1d0b6ee0e6f6a077e46eb2cd6fbb879dcb5ac6f646f07893f7a1"
+        );
+    }
+
+    // git ls-tree ef08d93 -- Service-A root-2 Service-B/file-B1 | tr / '|' | git mktree
+    #[test]
+    fn test_get_fingerprint_mktree_sha1_tree_order() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = vec![
+            Path::new("root-2"),
+            Path::new("Service-B/file-B1"),
+            Path::new("Service-A"),
+            Path::new("root-2"),
+            Path::new("not-found"),
+        ];
+        let tree_of_paths =
+            get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths).unwrap();
+        assert_eq!(
+            tree_of_paths
+                .get_fingerprint(FingerprintHash::Sha1)
+                .unwrap(),
+            "d4a8f5a3b5a8491c8b4a783acbf597adec093f82"
+        );
+    }
+
+    #[test]
+    fn test_get_fingerprint_empty_tree() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let tree_of_paths = get_tree_of_paths(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            &vec![Path::new("not-found")],
+        )
+        .unwrap();
+        assert_eq!(
+            tree_of_paths
+                .get_fingerprint(FingerprintHash::Sha1)
+                .unwrap(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+        );
+    }
+
+    #[test]
+    fn test_get_differing_paths() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = vec![
+            Path::new("root-1"),
+            Path::new("root-2"),
+            Path::new("Service-B"),
+        ];
+        let tree_01 =
+            get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths).unwrap();
+        let tree_07 =
+            get_tree_of_paths(&repo, "26b244f55f8886ccf6a47ea7f24498e9801fc38f", &paths).unwrap();
+        assert_eq!(
+            tree_01.get_differing_paths(&tree_07),
+            vec!["Service-B", "root-2"]
+        );
+        assert!(tree_07.get_differing_paths(&tree_07).is_empty());
+
+        let paths = vec![Path::new("Service-*/file-?2")];
+        let tree_01 =
+            get_tree_of_paths(&repo, "ef08d93fdeabf23734248d6f95ab4ff3952e9856", &paths).unwrap();
+        let tree_absent = get_tree_of_paths(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            &vec![Path::new("Service-A/file-A2"), Path::new("not-found")],
+        )
+        .unwrap();
+        assert_eq!(
+            tree_01.get_differing_paths(&tree_absent),
+            vec!["Service-B/file-B2", "not-found"]
+        );
+    }
+
+    #[test]
+    fn test_get_diff_stat() {
+        let (_tmp_dir, repo) = get_tmp_repo();
+        let paths = vec![
+            Path::new("root-1"),
+            Path::new("root-2"),
+            Path::new("Service-B"),
+            Path::new("!Service-B/file-B2"),
+        ];
+        let diff_stat = get_diff_stat(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            "26b244f55f8886ccf6a47ea7f24498e9801fc38f",
+            &paths,
+        )
+        .unwrap();
+        // Service-B/file-B2 is excluded
+        assert_eq!(
+            diff_stat,
+            " Service-B/file-B1 | 2 +-\n root-2            | 2 +-\n \
+             2 files changed, 2 insertions(+), 2 deletions(-)\n"
+        );
+        let paths = vec![Path::new("Service-B"), Path::new("!Service-B/*")];
+        let diff_stat = get_diff_stat(
+            &repo,
+            "ef08d93fdeabf23734248d6f95ab4ff3952e9856",
+            "26b244f55f8886ccf6a47ea7f24498e9801fc38f",
+            &paths,
+        )
+        .unwrap();
+        assert_eq!(diff_stat, "");
+        let err = get_diff_stat(
+            &repo,
+            "3333333333333333333333333333333333333333",
+            "26b244f",
+            &paths,
+        );
+        assert!(err.is_err());
     }
 }
@@ -0,0 +1,426 @@
+use crate::config::Config;
+use crate::find_last_job_ok::{is_job_name_ok, open_repository};
+use crate::history::{parse_history_line, HistoryRecord};
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use git2::{Oid, Repository, Signature};
+use std::path::Path;
+use std::process::{Command, Output};
+
+pub const DEFAULT_GIT_NOTES_REF: &str = "refs/notes/skip-ci";
+const GIT_NOTES_PUSH_ATTEMPT_MAX: u32 = 3;
+
+// a line of a skip-ci note : "fingerprint:job_id:ref:timestamp job_name", the newest first
+fn format_note_line(record: &HistoryRecord, job_name: &str) -> String {
+    format!("{record} {job_name}")
+}
+
+fn parse_note_line(line: &str) -> Option<(HistoryRecord, &str)> {
+    let (record, job_name) = line.trim().split_once(' ')?;
+    Some((parse_history_line(record)?, job_name))
+}
+
+// run git in the repository : the git command has the https and ssh transports and the
+// credential helpers that git2 (built without its default features) lacks
+fn run_git(config: &Config, args: &[&str]) -> anyhow::Result<Output> {
+    let git_dir = Path::new(&config.project_path).join(".git");
+    Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .args(args)
+        // never wait for a password
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .context("git command error")
+}
+
+// fetch the notes ref of the remote, the local notes ref is overwritten
+fn fetch_notes(config: &Config) -> anyhow::Result<()> {
+    let remote_name = config.git_notes_remote.as_str();
+    if remote_name.is_empty() {
+        verbose!("SKIP_CI_GIT_NOTES_REMOTE is empty → no fetch");
+        return Ok(());
+    }
+    let refspec = format!("+{0}:{0}", config.git_notes_ref);
+    verbose!("fetch {refspec} from {remote_name}");
+    let output = run_git(config, &["fetch", "--quiet", remote_name, &refspec])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() {
+        Ok(())
+    } else if stderr.contains("couldn't find remote ref") {
+        // the notes ref doesn't exist yet in the remote
+        verbose!("{} not found in {remote_name}", config.git_notes_ref);
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "git notes fetch from {remote_name} error: {}",
+            stderr.trim()
+        ))
+    }
+}
+
+// push the notes ref to the remote, return false if the push is rejected (not fast-forward)
+fn push_notes(config: &Config) -> anyhow::Result<bool> {
+    let remote_name = config.git_notes_remote.as_str();
+    if remote_name.is_empty() {
+        verbose!("SKIP_CI_GIT_NOTES_REMOTE is empty → no push");
+        return Ok(true);
+    }
+    let refspec = format!("{0}:{0}", config.git_notes_ref);
+    verbose!("push {refspec} to {remote_name}");
+    let output = run_git(config, &["push", "--porcelain", remote_name, &refspec])?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    // the porcelain status of a ref rejected by the remote because it isn't a fast-forward :
+    // "!<TAB>refs/notes/skip-ci:refs/notes/skip-ci<TAB>[rejected] (fetch first)"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.lines().any(|line| line.contains("\t[rejected]")) {
+        verbose!(
+            "push of {} rejected : {}",
+            config.git_notes_ref,
+            stdout.trim()
+        );
+        return Ok(false);
+    }
+    Err(anyhow!(
+        "git notes push to {remote_name} error: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+fn find_in_notes(
+    repo: &Repository,
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    let notes = match repo.notes(Some(&config.git_notes_ref)) {
+        Ok(notes) => notes,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => {
+            verbose!("{} doesn't exists", config.git_notes_ref);
+            return Ok(None);
+        }
+        Err(e) => return Err(e).context("git notes read error"),
+    };
+    let mut found: Option<HistoryRecord> = None;
+    for note in notes {
+        let (_, commit_id) = note.context("git notes read error")?;
+        let note = repo
+            .find_note(Some(&config.git_notes_ref), commit_id)
+            .context("git note read error")?;
+        let record = note
+            .message()
+            .unwrap_or_default()
+            .lines()
+            .filter_map(parse_note_line)
+            .filter(|(_, job_name)| is_job_name_ok(config, job_name))
+            .map(|(record, _)| record)
+            .find(|record| record.fingerprint == fingerprint);
+        if let Some(record) = record {
+            verbose!("record found in the note of {commit_id} : {record}");
+            // the notes aren't ordered : keep the newest record
+            if found
+                .as_ref()
+                .is_none_or(|f| f.timestamp < record.timestamp)
+            {
+                found = Some(record);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn add_note(
+    repo: &Repository,
+    config: &Config,
+    commit_id: Oid,
+    record: &HistoryRecord,
+) -> anyhow::Result<()> {
+    let previous = match repo.find_note(Some(&config.git_notes_ref), commit_id) {
+        Ok(note) => note.message().unwrap_or_default().to_string(),
+        Err(_) => String::new(),
+    };
+    let mut lines = vec![format_note_line(record, &config.ci_job_name)];
+    lines.extend(
+        previous
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(String::from),
+    );
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("skip-ci", "skip-ci@localhost"))
+        .context("git signature error")?;
+    repo.note(
+        &signature,
+        &signature,
+        Some(&config.git_notes_ref),
+        commit_id,
+        &(lines.join("\n") + "\n"),
+        true,
+    )
+    .context("git note write error")?;
+    Ok(())
+}
+
+// find the fingerprint in the notes of the repository, after a fetch of the notes of the remote
+pub fn find_in_git_notes(
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    let repo = open_repository(config)?;
+    if let Err(e) = fetch_notes(config) {
+        // the local notes are still usable
+        verbose!("{e:#}");
+    }
+    find_in_notes(&repo, config, fingerprint)
+}
+
+// read the local notes and list the notes ref of the remote, without fetching it : the fetch
+// would overwrite the local notes ref
+pub fn check_git_notes(config: &Config) -> anyhow::Result<String> {
+    let repo = open_repository(config)?;
+    find_in_notes(&repo, config, "")?;
+    let remote_name = config.git_notes_remote.as_str();
+    if remote_name.is_empty() {
+        return Ok("SKIP_CI_GIT_NOTES_REMOTE is empty → local notes only".to_string());
+    }
+    let output = run_git(config, &["ls-remote", remote_name, &config.git_notes_ref])?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git ls-remote {remote_name} error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let found = if output.stdout.is_empty() {
+        "not found"
+    } else {
+        "found"
+    };
+    Ok(format!("{} {found} in {remote_name}", config.git_notes_ref))
+}
+
+// add the record to the note of the HEAD commit and push the notes to the remote
+pub fn add_to_git_notes(config: &Config, record: &HistoryRecord) -> anyhow::Result<()> {
+    let repo = open_repository(config)?;
+    let head = repo
+        .refname_to_id("HEAD")
+        .context("Head retrieving error")?;
+    for attempt in 1..=GIT_NOTES_PUSH_ATTEMPT_MAX {
+        // another job may have pushed a note since the last fetch
+        fetch_notes(config)?;
+        add_note(&repo, config, head, record)?;
+        if push_notes(config)? {
+            return Ok(());
+        }
+        verbose!("git notes push rejected, attempt {attempt}/{GIT_NOTES_PUSH_ATTEMPT_MAX}");
+    }
+    Err(anyhow!(
+        "git notes push to {} rejected {GIT_NOTES_PUSH_ATTEMPT_MAX} times",
+        config.git_notes_remote
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::git_notes::{add_to_git_notes, check_git_notes, find_in_git_notes, parse_note_line};
+    use crate::history::parse_history_line;
+    use crate::history::tests::record;
+    use crate::process::tests::{create_config_ok, prepare_tmp_repo};
+    use git2::{Oid, Repository};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::Path;
+    use std::process::{Child, Command, Stdio};
+    use std::thread::sleep;
+    use std::time::Duration;
+    use tempfile::{tempdir, TempDir};
+
+    const FINGERPRINT: &str = "3de26cd90c29f5a3e937bca57e603b1f58f00026";
+    const HEAD: &str = "26b244f55f8886ccf6a47ea7f24498e9801fc38f";
+
+    fn create_config(tmp_dir: &TempDir, remote_dir: &TempDir) -> Config {
+        Config {
+            backend: Backend::GitNotes,
+            git_notes_remote: remote_dir.path().to_str().unwrap().to_string(),
+            ..create_config_ok(tmp_dir, "____")
+        }
+    }
+
+    // a git daemon serving the repositories of base_path, push enabled
+    struct GitDaemon(Child);
+
+    impl Drop for GitDaemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_git_daemon(base_path: &Path) -> (GitDaemon, u16) {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        // git-daemon itself : "git daemon" would leave it running after the kill of git
+        let exec_path = Command::new("git").arg("--exec-path").output().unwrap();
+        let exec_path = String::from_utf8(exec_path.stdout).unwrap();
+        let daemon = Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+            .arg("--listen=127.0.0.1")
+            .arg(format!("--port={port}"))
+            .arg(format!("--base-path={}", base_path.display()))
+            .arg("--export-all")
+            .arg("--enable=receive-pack")
+            .arg("--reuseaddr")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        for _ in 0..50 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                break;
+            }
+            sleep(Duration::from_millis(100));
+        }
+        (GitDaemon(daemon), port)
+    }
+
+    #[test]
+    fn test_parse_note_line() {
+        assert_eq!(
+            parse_note_line("abc:456:main:1700000000 build job"),
+            Some((
+                parse_history_line("abc:456:main:1700000000").unwrap(),
+                "build job"
+            ))
+        );
+        assert_eq!(parse_note_line("abc:456:main:1700000000"), None);
+        assert_eq!(parse_note_line("abc jobA"), None);
+    }
+
+    #[test]
+    fn test_add_to_git_notes() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let remote_dir = tempdir().unwrap();
+        let remote = Repository::init_bare(remote_dir.path()).unwrap();
+        let config = create_config(&tmp_dir, &remote_dir);
+
+        add_to_git_notes(&config, &record(FINGERPRINT, 1)).unwrap();
+        add_to_git_notes(&config, &record(FINGERPRINT, 2)).unwrap();
+
+        let note = remote
+            .find_note(Some("refs/notes/skip-ci"), Oid::from_str(HEAD).unwrap())
+            .unwrap();
+        assert_eq!(
+            note.message().unwrap(),
+            format!("{FINGERPRINT}:2:main:1700000000 jobA\n{FINGERPRINT}:1:main:1700000000 jobA\n")
+        );
+    }
+
+    #[test]
+    fn test_find_in_git_notes() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let remote_dir = tempdir().unwrap();
+        Repository::init_bare(remote_dir.path()).unwrap();
+        let config = create_config(&tmp_dir, &remote_dir);
+        assert_eq!(find_in_git_notes(&config, FINGERPRINT).unwrap(), None);
+
+        add_to_git_notes(&config, &record(FINGERPRINT, 1)).unwrap();
+        add_to_git_notes(&config, &record(FINGERPRINT, 2)).unwrap();
+
+        // a fresh clone : the notes are fetched from the remote
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = create_config(&tmp_dir, &remote_dir);
+        let found = find_in_git_notes(&config, FINGERPRINT).unwrap().unwrap();
+        assert_eq!(found.job_id, 2);
+        assert_eq!(find_in_git_notes(&config, "abc").unwrap(), None);
+
+        // the notes of another job are ignored
+        let config = Config {
+            ci_job_name: "jobB".to_string(),
+            ..config
+        };
+        assert_eq!(find_in_git_notes(&config, FINGERPRINT).unwrap(), None);
+    }
+
+    #[test]
+    fn test_git_notes_daemon_remote() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let remote_dir = tempdir().unwrap();
+        Repository::init_bare(remote_dir.path().join("project.git")).unwrap();
+        let (_daemon, port) = start_git_daemon(remote_dir.path());
+        let config = Config {
+            git_notes_remote: format!("git://127.0.0.1:{port}/project.git"),
+            ..create_config(&tmp_dir, &remote_dir)
+        };
+        assert_eq!(find_in_git_notes(&config, FINGERPRINT).unwrap(), None);
+        add_to_git_notes(&config, &record(FINGERPRINT, 1)).unwrap();
+        add_to_git_notes(&config, &record(FINGERPRINT, 2)).unwrap();
+
+        // a fresh clone : the notes are fetched through the daemon
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            project_path: tmp_dir.path().to_str().unwrap().to_string(),
+            ..config
+        };
+        let found = find_in_git_notes(&config, FINGERPRINT).unwrap().unwrap();
+        assert_eq!(found.job_id, 2);
+    }
+
+    #[test]
+    fn test_git_notes_without_remote() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            backend: Backend::GitNotes,
+            git_notes_remote: "".to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        assert_eq!(find_in_git_notes(&config, FINGERPRINT).unwrap(), None);
+        add_to_git_notes(&config, &record(FINGERPRINT, 1)).unwrap();
+        let found = find_in_git_notes(&config, FINGERPRINT).unwrap().unwrap();
+        assert_eq!(found.job_id, 1);
+    }
+
+    #[test]
+    fn test_git_notes_remote_ko() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let remote_dir = tempdir().unwrap();
+        let config = Config {
+            git_notes_remote: remote_dir.path().join("nope").to_str().unwrap().to_string(),
+            ..create_config(&tmp_dir, &remote_dir)
+        };
+        // the lookup falls back on the local notes
+        assert_eq!(find_in_git_notes(&config, FINGERPRINT).unwrap(), None);
+        assert!(add_to_git_notes(&config, &record(FINGERPRINT, 1)).is_err());
+    }
+
+    #[test]
+    fn test_check_git_notes() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let remote_dir = tempdir().unwrap();
+        Repository::init_bare(remote_dir.path()).unwrap();
+        let config = create_config(&tmp_dir, &remote_dir);
+        let remote = remote_dir.path().to_str().unwrap();
+        assert_eq!(
+            check_git_notes(&config).unwrap(),
+            format!("refs/notes/skip-ci not found in {remote}")
+        );
+        add_to_git_notes(&config, &record(FINGERPRINT, 1)).unwrap();
+
+        // a fresh clone : the notes of the remote aren't fetched
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let config = create_config(&tmp_dir, &remote_dir);
+        assert_eq!(
+            check_git_notes(&config).unwrap(),
+            format!("refs/notes/skip-ci found in {remote}")
+        );
+        assert!(repo.refname_to_id("refs/notes/skip-ci").is_err());
+
+        let config = Config {
+            git_notes_remote: remote_dir.path().join("nope").to_str().unwrap().to_string(),
+            ..config
+        };
+        assert!(check_git_notes(&config).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// minimal glob matching of the SKIP_IF_TREE_OK_IN_PAST paths :
+//  - `*` matches any sequence of characters except `/`
+//  - `?` matches one character except `/`
+//  - `[abc]`, `[a-z]`, `[!a]` match one character of the class
+//  - `**` as a full path segment matches zero or more segments
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+// check if the path matches the pattern
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    match_segments(&split(pattern), &split(path), false)
+}
+
+// check if a path under the directory "dir_path" could match the pattern
+pub fn glob_match_dir_prefix(pattern: &str, dir_path: &str) -> bool {
+    match_segments(&split(pattern), &split(dir_path), true)
+}
+
+fn split(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn match_segments(pattern: &[&str], path: &[&str], prefix: bool) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", pattern_rest)) => {
+            (0..=path.len()).any(|i| match_segments(pattern_rest, &path[i..], prefix))
+        }
+        Some((segment_pattern, pattern_rest)) => match path.split_first() {
+            None => prefix,
+            Some((segment, path_rest)) => {
+                let pattern_chars = segment_pattern.chars().collect::<Vec<char>>();
+                let segment_chars = segment.chars().collect::<Vec<char>>();
+                match_segment(&pattern_chars, &segment_chars)
+                    && match_segments(pattern_rest, path_rest, prefix)
+            }
+        },
+    }
+}
+
+fn match_segment(pattern: &[char], segment: &[char]) -> bool {
+    match pattern.split_first() {
+        None => segment.is_empty(),
+        Some(('*', pattern_rest)) => {
+            (0..=segment.len()).any(|i| match_segment(pattern_rest, &segment[i..]))
+        }
+        Some(('?', pattern_rest)) => {
+            !segment.is_empty() && match_segment(pattern_rest, &segment[1..])
+        }
+        Some(('[', _)) => match (parse_class(pattern), segment.split_first()) {
+            (Some((matcher, pattern_rest)), Some((c, segment_rest))) => {
+                matcher(*c) && match_segment(pattern_rest, segment_rest)
+            }
+            // unclosed class : "[" is a literal char
+            (None, Some(('[', segment_rest))) => match_segment(&pattern[1..], segment_rest),
+            _ => false,
+        },
+        Some((p, pattern_rest)) => match segment.split_first() {
+            Some((c, segment_rest)) => p == c && match_segment(pattern_rest, segment_rest),
+            None => false,
+        },
+    }
+}
+
+type ClassMatcher = Box<dyn Fn(char) -> bool>;
+
+// parse the "[...]" class at the start of the pattern, return the matcher and the pattern rest
+fn parse_class(pattern: &[char]) -> Option<(ClassMatcher, &[char])> {
+    let mut index = 1;
+    let negated = matches!(pattern.get(index), Some('!') | Some('^'));
+    if negated {
+        index += 1;
+    }
+    let start = index;
+    // a "]" just after the "[" or "[!" is a literal char
+    if pattern.get(index) == Some(&']') {
+        index += 1;
+    }
+    while pattern.get(index)? != &']' {
+        index += 1;
+    }
+    let class = pattern[start..index].to_vec();
+    let matcher = move |c: char| {
+        let mut found = false;
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                found |= class[i] <= c && c <= class[i + 2];
+                i += 3;
+            } else {
+                found |= class[i] == c;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((Box::new(matcher), &pattern[index + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glob::{glob_match, glob_match_dir_prefix, is_glob};
+
+    #[test]
+    fn test_is_glob() {
+        assert!(is_glob("services/*/proto"));
+        assert!(is_glob("**/Cargo.toml"));
+        assert!(is_glob("file-?"));
+        assert!(is_glob("file-[AB]"));
+        assert!(!is_glob("Service-A/file-A1"));
+    }
+
+    #[test]
+    fn test_glob_match_star() {
+        assert!(glob_match("services/*/proto", "services/a/proto"));
+        assert!(!glob_match("services/*/proto", "services/a/b/proto"));
+        assert!(glob_match("libs/common-*", "libs/common-"));
+        assert!(glob_match("libs/common-*", "libs/common-utils"));
+        assert!(!glob_match("libs/common-*", "libs/other"));
+        assert!(!glob_match("libs/common-*", "libs/common-utils/src"));
+        assert!(glob_match("*.md", "README.md"));
+        assert!(!glob_match("*.md", "docs/README.md"));
+    }
+
+    #[test]
+    fn test_glob_match_double_star() {
+        assert!(glob_match("**/Cargo.toml", "Cargo.toml"));
+        assert!(glob_match("**/Cargo.toml", "a/b/c/Cargo.toml"));
+        assert!(!glob_match("**/Cargo.toml", "a/b/c/Cargo.lock"));
+        assert!(glob_match("a/**", "a/b/c"));
+        assert!(glob_match("a/**/z", "a/z"));
+        assert!(glob_match("a/**/z", "a/b/c/z"));
+        assert!(!glob_match("a/**/z", "b/z"));
+    }
+
+    #[test]
+    fn test_glob_match_question_mark_and_class() {
+        assert!(glob_match("file-?1", "file-A1"));
+        assert!(!glob_match("file-?1", "file-1"));
+        assert!(glob_match("file-[AB]1", "file-B1"));
+        assert!(!glob_match("file-[AB]1", "file-C1"));
+        assert!(glob_match("file-[a-c]", "file-b"));
+        assert!(!glob_match("file-[!a-c]", "file-b"));
+        assert!(glob_match("file-[!a-c]", "file-d"));
+        assert!(glob_match("file-[]]", "file-]"));
+        assert!(glob_match("file-[", "file-["));
+    }
+
+    #[test]
+    fn test_glob_match_dir_prefix() {
+        assert!(glob_match_dir_prefix("services/*/proto", "services"));
+        assert!(glob_match_dir_prefix("services/*/proto", "services/a"));
+        assert!(!glob_match_dir_prefix("services/*/proto", "libs"));
+        assert!(!glob_match_dir_prefix("services/*/proto", "services/a/b"));
+        assert!(glob_match_dir_prefix("**/Cargo.toml", "a/b/c"));
+    }
+}
@@ -101,17 +101,150 @@ Usage in .gitlab-ci.yml file :
         - ./skip-if-tree-ok-in-past || service-A/test2.sh
         - ./skip-if-tree-ok-in-past || service-A/test3.sh
 
+The "record" subcommand, to run in the after_script, adds the
+"fingerprint:CI_JOB_ID:CI_COMMIT_REF_NAME:timestamp" line to the history store of
+the backend if CI_JOB_STATUS=success :
+    after_script:
+        - ./skip-if-tree-ok-in-past record
+
+Usage : skip-if-tree-ok-in-past [command] [--flag value]...
+The commands :
+- check: [default] search the tree and write the ci-skip file, exit 0 if found
+- fingerprint: print the "<filemode> <id> <path>" entries and the fingerprint
+  of a commit (--commit <commit-ish>, HEAD by default) as text or JSON
+  (--format text|json), the CI_* variables and the API are not required
+  (CI_PROJECT_DIR defaults to the current directory)
+- explain: list the jobs scanned (the pipelines of the ancestors with
+  SKIP_CI_SEARCH=ancestors) and why each one matches or not (different
+  name, commit missing from the clone, paths differing), with the diff stat of
+  the closest job, without the artifacts and the ci-skip file
+- record: add the fingerprint to the history store, in the after_script
+- doctor: check the config, the repository and the backend
+- help: print this help
+The flags override the environment variables and the config file :
+--job (CI_JOB_NAME), --paths (SKIP_IF_TREE_OK_IN_PAST), --project-dir
+(CI_PROJECT_DIR), --project-id (CI_PROJECT_ID), --api-url (CI_API_V4_URL),
+--dry-run (SKIP_CI_DRY_RUN=true)
+Outside of a job (fingerprint, explain, doctor), CI_JOB_ID is not required.
+
 The skip-if-tree-ok-in-past environment variables :
-- SKIP_IF_TREE_OK_IN_PAST: [required]  must contain the paths used by the job
+- SKIP_IF_TREE_OK_IN_PAST: [required]  must contain the paths used by the job,
+  glob patterns are supported : services/*/proto, **/Cargo.toml, libs/common-*
+  The paths prefixed by "!" are excluded : service-A !service-A/docs !**/*.md
+- the paths absent from a commit are part of the fingerprint as "absent"
+- .skipciignore file : the exclusion patterns of this file (at the root of the
+  project, "#" for the comments) are added to the exclusions of
+  SKIP_IF_TREE_OK_IN_PAST. The negated patterns (!keep.md) are rejected
+- SKIP_CI_EXCLUDES: the exclusion patterns (separated by spaces) added to the
+  exclusions of SKIP_IF_TREE_OK_IN_PAST, "excludes" in the config file
 - API_READ_TOKEN: [required] project access tokens that have read_api scope
+- SKIP_CI_API_AUTH: [default=private-token] the header of API_READ_TOKEN :
+  "private-token" (PRIVATE-TOKEN, the project, group and personal access
+  tokens) or "bearer" (Authorization: Bearer, the OAuth2 tokens). The tokens
+  are sent in the headers, never in the urls : CI_JOB_TOKEN in JOB-TOKEN
+- SKIP_CI_JOB_NAME_MATCH: [default=exact] how the names of the jobs are
+  compared with CI_JOB_NAME : "exact" (each shard of parallel reuses the same
+  shard "test 1/3", each job of parallel:matrix the same tuple
+  "build: [linux, amd64]"), "parallel" (the name without the index :
+  "test 1/3" reuses any shard, "test 2/3", "test 1/4" or "test", for the
+  shards that run the same tests), "matrix" (the same name and the same tuple
+  whatever its spacing : "build: [linux, amd64]" reuses
+  "build: [linux,amd64]") or "regex" (the names that match
+  SKIP_CI_JOB_NAME_REGEX, e.g. other tuples of parallel:matrix)
+- SKIP_CI_JOB_NAME_REGEX: the regex of the job names with
+  SKIP_CI_JOB_NAME_MATCH=regex
+- SKIP_CI_JOB_NAME_ALIASES: the other job names whose success counts for
+  the current job, separated by ";" (a renamed job, or "test" split into
+  "test-unit" and "test-int"), job_name_aliases = ["test"] in the config file.
+  Used by the api and git-notes backends. The fs, s3 and registry stores are
+  keyed by the job name : the records of the aliases are looked up after the
+  records of the job, by their exact names (SKIP_CI_JOB_NAME_MATCH is not
+  applied, a warning in verbose mode)
 - SKIP_CI_COMMIT_TO_CHECK_SAME_JOB_MAX: [default=100] stop check if
   this limit is reached
 - SKIP_CI_COMMIT_TO_CHECK_SAME_REF_MAX: [default=3] stop check if this
   limit is reached
 - SKIP_CI_PAGE_TO_FETCH_MAX: [default=5] max queries to the gitlabAPI
-  /jobs
-- SKIP_CI_VERBOSE: [default=false] "true" to enable verbose log
+  /jobs, the search stops before at the last page (Link: rel="next" or
+  X-Next-Page headers)
+- SKIP_CI_PAGE_PREFETCH_MAX: [default=2] number of the next pages of jobs
+  downloaded while the current page is checked, with the offset pagination
+  (0 to disable)
+- SKIP_CI_SEARCH: [default=jobs] "jobs" (the last successful jobs of the
+  project, page by page) or "ancestors" (the ancestors of HEAD with the same
+  fingerprint, found in the local git history, then the jobs of their
+  pipelines /pipelines?sha=)
+- SKIP_CI_ANCESTOR_TO_CHECK_MAX: [default=100] max commits of the history of
+  HEAD compared with SKIP_CI_SEARCH=ancestors (the clone must be deep enough :
+  GIT_DEPTH)
+- SKIP_CI_ANCESTOR_QUERY_MAX: [default=10] max ancestors with the same
+  fingerprint whose pipelines are queried with SKIP_CI_SEARCH=ancestors
+- SKIP_CI_PAGINATION: [default=offset] "offset" (page=N) or "keyset"
+  (pagination=keyset&order_by=id&sort=desc, the next pages of the Link header)
+  if the Gitlab version supports the keyset pagination of the jobs
+- SKIP_CI_VERBOSE: [default=false] "true" to enable verbose log, the tokens
+  (private_token=, job_token=, the user:password@ of the urls and the config)
+  are masked : [MASKED]
+- SKIP_CI_BACKEND: [default=api] "api" to search in the last jobs of the
+  project with the Gitlab API, "cache" to search in the "ci_ok_history" file of
+  the Gitlab cache, "git-notes" to search in the git notes of the repository,
+  "fs" to search in a directory of a shared filesystem, "s3" to search in a
+  bucket of an S3-compatible object storage, "registry" to search in the
+  generic package registry of the project (API_READ_TOKEN is not needed)
+- SKIP_CI_GIT_NOTES_REF: [default=refs/notes/skip-ci] the notes ref of the
+  git-notes backend
+- SKIP_CI_GIT_NOTES_REMOTE: [default=origin] the remote (name or URL) of the
+  notes, fetched and pushed with the git command, empty to only use the local
+  notes. The push needs a write-capable remote (a token with the
+  write_repository scope in the URL)
+- SKIP_CI_FS_ROOT: [required by the fs backend] the root directory of the
+  <SKIP_CI_FS_ROOT>/<CI_PROJECT_ID>/<CI_JOB_NAME>/<fingerprint>.json entries
+- SKIP_CI_FS_MAX_AGE_DAYS: [default=30] the max age of the entries of the fs
+  backend
+- SKIP_CI_S3_ENDPOINT, SKIP_CI_S3_BUCKET, SKIP_CI_S3_ACCESS_KEY_ID,
+  SKIP_CI_S3_SECRET_ACCESS_KEY: [required by the s3 backend] the url of the
+  object storage, the bucket and the credentials
+- SKIP_CI_S3_PREFIX: [default=skip-ci] the prefix of the keys
+- SKIP_CI_S3_REGION: [default=us-east-1] the region of the signature
+- SKIP_CI_S3_ARTIFACT_PATHS: [default=] the paths to zip and upload with the
+  record (without the symbolic links), extracted instead of the Gitlab artifacts
+- SKIP_CI_NO_ARTIFACT: [default=false] "true" to disable artifacts download
+- SKIP_CI_VALUE: [default=] "true" or "false" to force the result
+- SKIP_CI_FINGERPRINT_HASH: [default=sha1] "sha1" or "sha256", the hash of the
+  fingerprint : the tree created by
+  git ls-tree HEAD -- $SKIP_IF_TREE_OK_IN_PAST | tr / '|' | git mktree
 - SKIP_SKIP_CI: [default=false] "true" to skip the check, always exit "3"
+- SKIP_CI_DRY_RUN: [default=false] "true" (or the --dry-run flag) to search
+  without side effect : no artifacts download, no ci-skip file, no record of the
+  cache backend, no [skip-ci-oldest-ancestor] line in the log, exit "4"
+  instead of "0" if the tree is found
+- SKIP_CI_CONFIG_FILE: [default=$CI_PROJECT_DIR/.skip-if-tree-ok-in-past.toml]
+  the config file (TOML, or YAML if its extension is .yml or .yaml) : a
+  [default] section and [jobs."<CI_JOB_NAME>"] sections, the keys are the
+  lowercase variables without SKIP_CI_ (paths for SKIP_IF_TREE_OK_IN_PAST),
+  the environment variables override the file
+- SKIP_CI_HTTP_CONNECT_TIMEOUT: [default=10] the connect timeout (seconds) of
+  the requests to Gitlab, S3 and the registry
+- SKIP_CI_HTTP_READ_TIMEOUT: [default=30] the max wait (seconds) of the
+  response headers and of each chunk of the body
+- SKIP_CI_HTTP_RETRY_MAX: [default=3] the retries of the connection errors,
+  the timeouts, 429 and 5xx, after an exponential backoff (1s, 2s, 4s...) or
+  the delay of the Retry-After and RateLimit-Reset headers (60s max)
+- HTTPS_PROXY, HTTP_PROXY (or https_proxy, http_proxy): the proxy of the https
+  and http urls, http://[user:password@]host[:port] (default port 1080). The
+  https requests go through a CONNECT tunnel, the http requests are sent to
+  the proxy, with the Proxy-Authorization: Basic header if the user is set
+- NO_PROXY (or no_proxy): the hosts reached without proxy, separated by
+  commas : localhost,.internal.corp (the subdomains match too), * for all.
+  The ports are ignored : gitlab.corp:8443 matches gitlab.corp
+- SKIP_CI_CA_FILE, CI_SERVER_TLS_CA_FILE, SSL_CERT_FILE: the PEM files of the
+  CA certificates added to the webpki roots : the internal CA of a
+  self-managed Gitlab. The runners set CI_SERVER_TLS_CA_FILE. A missing
+  CI_SERVER_TLS_CA_FILE or SSL_CERT_FILE is skipped (a warning in verbose
+  mode), a missing SKIP_CI_CA_FILE is an error
+- SKIP_CI_TLS_CERT_FILE, SKIP_CI_TLS_KEY_FILE (or CI_SERVER_TLS_CERT_FILE,
+  CI_SERVER_TLS_KEY_FILE): the PEM files of the client certificate and of its
+  private key (PKCS8, RSA or EC) for the mTLS
 "###;
 
 pub fn get_version_msg() -> String {
@@ -0,0 +1,314 @@
+use crate::artifact::extract_artifacts_of_job_id;
+use crate::config::{Backend, Config};
+use crate::fs_store::{add_to_fs_store, find_in_fs_store};
+use crate::git_notes::{add_to_git_notes, find_in_git_notes};
+use crate::job_name::JobNameMatch;
+use crate::registry_store::{add_to_registry, find_in_registry};
+use crate::s3_store::{add_to_s3_store, extract_s3_artifact, find_in_s3_store};
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+pub const CI_OK_HISTORY_FILE: &str = "ci_ok_history";
+const CI_OK_HISTORY_MAX: usize = 500;
+
+// a line of the ci_ok_history file : "fingerprint:job_id", the format of the bash/go/node cache
+// versions, optionally followed by ":ref:timestamp"
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub fingerprint: String,
+    pub job_id: u64,
+    pub job_ref: Option<String>,
+    pub timestamp: Option<u64>,
+}
+
+impl Display for HistoryRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.fingerprint, self.job_id)?;
+        if let Some(job_ref) = &self.job_ref {
+            write!(f, ":{job_ref}:{}", self.timestamp.unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+impl HistoryRecord {
+    // the record of the current job : fingerprint, CI_JOB_ID, CI_COMMIT_REF_NAME and timestamp
+    pub fn of_current_job(config: &Config, fingerprint: &str) -> HistoryRecord {
+        HistoryRecord {
+            fingerprint: fingerprint.to_string(),
+            job_id: config.ci_job_id,
+            job_ref: Some(config.ci_commit_ref_name.clone().unwrap_or_default()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .ok(),
+        }
+    }
+}
+
+pub fn parse_history_line(line: &str) -> Option<HistoryRecord> {
+    let mut fields = line.trim().split(':');
+    let fingerprint = fields.next().filter(|f| !f.is_empty())?.to_string();
+    let job_id = fields.next()?.parse::<u64>().ok()?;
+    let job_ref = fields.next().map(String::from);
+    let timestamp = fields.next().and_then(|t| t.parse::<u64>().ok());
+    Some(HistoryRecord {
+        fingerprint,
+        job_id,
+        job_ref,
+        timestamp,
+    })
+}
+
+// extract the artifacts of the record : the zip of the s3 backend, else the artifacts of the job
+pub async fn extract_artifacts_of_record(
+    config: &Config,
+    record: &HistoryRecord,
+) -> anyhow::Result<bool> {
+    match config.backend {
+        Backend::S3 if !config.s3_artifact_paths.is_empty() => {
+            extract_s3_artifact(config, record).await
+        }
+        _ => extract_artifacts_of_job_id(config, record.job_id).await,
+    }
+}
+
+// the fs, s3 and registry stores are keyed by the job name : only the exact names of the job and
+// of its aliases are looked up
+fn warn_job_name_match(config: &Config) {
+    if config.job_name_match != JobNameMatch::Exact {
+        verbose!(
+            "warning : SKIP_CI_JOB_NAME_MATCH={} is not applied to the {:?} backend, the records \
+            of {} and of its aliases are looked up",
+            config.job_name_match,
+            config.backend,
+            config.ci_job_name
+        );
+    }
+}
+
+// find the fingerprint in the history store of the backend
+pub async fn find_in_history(
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    if matches!(
+        config.backend,
+        Backend::Fs | Backend::S3 | Backend::Registry
+    ) {
+        warn_job_name_match(config);
+    }
+    match config.backend {
+        Backend::Cache => find_in_ci_ok_history(&config.ci_ok_history_path, fingerprint).await,
+        Backend::GitNotes => find_in_git_notes(config, fingerprint),
+        Backend::Fs => find_in_fs_store(config, fingerprint),
+        Backend::S3 => find_in_s3_store(config, fingerprint).await,
+        Backend::Registry => find_in_registry(config, fingerprint).await,
+        Backend::Api => Err(anyhow!("the api backend has no history store")),
+    }
+}
+
+// add the record to the history store of the backend, return false if the backend has no store
+pub async fn record_in_history(config: &Config, record: &HistoryRecord) -> anyhow::Result<bool> {
+    match config.backend {
+        Backend::Cache => {
+            append_to_ci_ok_history(&config.ci_ok_history_path, record).await?;
+            Ok(true)
+        }
+        Backend::GitNotes => {
+            add_to_git_notes(config, record)?;
+            Ok(true)
+        }
+        Backend::Fs => {
+            add_to_fs_store(config, record)?;
+            Ok(true)
+        }
+        Backend::S3 => {
+            add_to_s3_store(config, record).await?;
+            Ok(true)
+        }
+        Backend::Registry => {
+            add_to_registry(config, record).await?;
+            Ok(true)
+        }
+        Backend::Api => Ok(false),
+    }
+}
+
+// find the fingerprint in the ci_ok_history file, the newest records are at the end (read with
+// "tac" by the bash and go versions)
+pub async fn find_in_ci_ok_history(
+    history_path: &str,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    if !fs::try_exists(history_path).await.unwrap_or(false) {
+        verbose!("{history_path} doesn't exists");
+        return Ok(None);
+    }
+    let history = fs::read_to_string(history_path)
+        .await
+        .context("read ci_ok_history error")?;
+    let record = history
+        .lines()
+        .rev()
+        .filter_map(parse_history_line)
+        .find(|record| record.fingerprint == fingerprint);
+    verbose!("record found in {history_path} : {record:?}");
+    Ok(record)
+}
+
+// add the record at the end of the ci_ok_history file, keep the CI_OK_HISTORY_MAX last
+pub async fn append_to_ci_ok_history(
+    history_path: &str,
+    record: &HistoryRecord,
+) -> anyhow::Result<()> {
+    verbose!("append {record} to {history_path}");
+    let history = if Path::new(history_path).exists() {
+        fs::read_to_string(history_path)
+            .await
+            .context("read ci_ok_history error")?
+    } else {
+        String::new()
+    };
+    let mut lines = history
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        // the new record replaces the record of the same job
+        .filter(|line| {
+            parse_history_line(line)
+                .is_none_or(|r| r.fingerprint != record.fingerprint || r.job_id != record.job_id)
+        })
+        .collect::<Vec<&str>>();
+    let record_line = record.to_string();
+    lines.push(&record_line);
+    let first = lines.len().saturating_sub(CI_OK_HISTORY_MAX);
+    fs::write(history_path, lines[first..].join("\n") + "\n")
+        .await
+        .context("write ci_ok_history error")?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::history::{
+        append_to_ci_ok_history, find_in_ci_ok_history, parse_history_line, HistoryRecord,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    // a record of the branch main, the record of the history stores tests
+    pub fn record(fingerprint: &str, job_id: u64) -> HistoryRecord {
+        HistoryRecord {
+            fingerprint: fingerprint.to_string(),
+            job_id,
+            job_ref: Some("main".to_string()),
+            timestamp: Some(1700000000),
+        }
+    }
+
+    #[test]
+    fn test_parse_history_line() {
+        assert_eq!(
+            parse_history_line("3de26cd90c29f5a3e937bca57e603b1f58f00026:456"),
+            Some(HistoryRecord {
+                fingerprint: "3de26cd90c29f5a3e937bca57e603b1f58f00026".to_string(),
+                job_id: 456,
+                job_ref: None,
+                timestamp: None,
+            })
+        );
+        assert_eq!(
+            parse_history_line("abc:456:main:1700000000"),
+            Some(HistoryRecord {
+                fingerprint: "abc".to_string(),
+                job_id: 456,
+                job_ref: Some("main".to_string()),
+                timestamp: Some(1700000000),
+            })
+        );
+        assert_eq!(parse_history_line(""), None);
+        assert_eq!(parse_history_line("abc"), None);
+        assert_eq!(parse_history_line("abc:def"), None);
+    }
+
+    #[test]
+    fn test_history_record_display() {
+        let record = HistoryRecord {
+            fingerprint: "abc".to_string(),
+            job_id: 456,
+            job_ref: None,
+            timestamp: None,
+        };
+        assert_eq!(record.to_string(), "abc:456");
+        let record = HistoryRecord {
+            job_ref: Some("main".to_string()),
+            timestamp: Some(1700000000),
+            ..record
+        };
+        assert_eq!(record.to_string(), "abc:456:main:1700000000");
+    }
+
+    #[tokio::test]
+    async fn test_find_in_ci_ok_history() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("ci_ok_history");
+        let path = path.to_str().unwrap();
+        assert_eq!(find_in_ci_ok_history(path, "abc").await.unwrap(), None);
+        fs::write(path, "abc:1\nabc:2\ndef:3\n").unwrap();
+        let record = find_in_ci_ok_history(path, "abc").await.unwrap().unwrap();
+        assert_eq!(record.job_id, 2);
+        assert_eq!(find_in_ci_ok_history(path, "ab").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_append_to_ci_ok_history() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("ci_ok_history");
+        let path = path.to_str().unwrap();
+        let record = HistoryRecord {
+            fingerprint: "abc".to_string(),
+            job_id: 1,
+            job_ref: None,
+            timestamp: None,
+        };
+        append_to_ci_ok_history(path, &record).await.unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "abc:1\n");
+        let record = HistoryRecord {
+            job_id: 2,
+            ..record
+        };
+        append_to_ci_ok_history(path, &record).await.unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), "abc:1\nabc:2\n");
+        let record = HistoryRecord {
+            job_id: 1,
+            job_ref: Some("main".to_string()),
+            timestamp: Some(1700000000),
+            ..record
+        };
+        append_to_ci_ok_history(path, &record).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "abc:2\nabc:1:main:1700000000\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_append_to_ci_ok_history_max() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("ci_ok_history");
+        let path = path.to_str().unwrap();
+        let history = (0..600).map(|i| format!("f{i}:{i}\n")).collect::<String>();
+        fs::write(path, history).unwrap();
+        let record = parse_history_line("new:1000").unwrap();
+        append_to_ci_ok_history(path, &record).await.unwrap();
+        let content = fs::read_to_string(path).unwrap();
+        assert_eq!(content.lines().count(), 500);
+        assert!(content.starts_with("f101:101\n"));
+        assert!(content.ends_with("f599:599\nnew:1000\n"));
+    }
+}
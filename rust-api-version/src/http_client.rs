@@ -0,0 +1,407 @@
+use crate::proxy::{Proxies, ProxyConnector};
+use crate::tls::{get_client_config, TlsSettings};
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use hyper::body::{Bytes, HttpBody};
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, HeaderValue, PROXY_AUTHORIZATION, RETRY_AFTER};
+use hyper::{Body, Client, Request, Response, StatusCode};
+use hyper_rustls::HttpsConnector;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, timeout};
+
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_RETRY_MAX: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+// the retries must not cost more than the job itself
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+type HttpsClient = Client<HttpsConnector<ProxyConnector>>;
+
+// the client of all the requests of the job and its connection pool, built by the first request
+// and shared by the clones of the config (the prefetch tasks of the pages included) : each test
+// has its own config, its own client and its own runtime
+#[derive(Clone, Default)]
+pub struct SharedClient(Arc<OnceLock<HttpsClient>>);
+
+impl SharedClient {
+    fn get(&self, settings: &HttpSettings) -> anyhow::Result<HttpsClient> {
+        if let Some(client) = self.0.get() {
+            return Ok(client.clone());
+        }
+        let client = build_client(settings)?;
+        Ok(self.0.get_or_init(|| client).clone())
+    }
+}
+
+impl Debug for SharedClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SharedClient")
+    }
+}
+
+// the client is built from the other settings
+impl PartialEq for SharedClient {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpSettings {
+    // SKIP_CI_HTTP_CONNECT_TIMEOUT
+    pub connect_timeout: Duration,
+    // SKIP_CI_HTTP_READ_TIMEOUT : the max wait of the response headers and of each body chunk
+    pub read_timeout: Duration,
+    // SKIP_CI_HTTP_RETRY_MAX
+    pub retry_max: u32,
+    // the delay of the first retry, doubled on each retry
+    pub retry_base_delay: Duration,
+    // HTTPS_PROXY, HTTP_PROXY and NO_PROXY
+    pub proxies: Proxies,
+    // the CA files and the client certificate
+    pub tls: TlsSettings,
+    pub client: SharedClient,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS),
+            read_timeout: Duration::from_secs(DEFAULT_READ_TIMEOUT_SECS),
+            retry_max: DEFAULT_RETRY_MAX,
+            retry_base_delay: RETRY_BASE_DELAY,
+            proxies: Proxies::default(),
+            tls: TlsSettings::default(),
+            client: SharedClient::default(),
+        }
+    }
+}
+
+fn build_client(settings: &HttpSettings) -> anyhow::Result<HttpsClient> {
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(Some(settings.connect_timeout));
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(get_client_config(&settings.tls).context("TLS config error")?)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(ProxyConnector {
+            http,
+            proxies: settings.proxies.clone(),
+        });
+    Ok(Client::builder().build(https))
+}
+
+// 429 and 5xx are transient : Gitlab is rate limiting, restarting or behind a busy proxy
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn get_header_secs(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+// the delay before the retry "attempt" (from 0) : Retry-After (seconds), then RateLimit-Reset
+// if RateLimit-Remaining is 0 (a unix timestamp on Gitlab, seconds otherwise), then the
+// exponential backoff, bounded by RETRY_MAX_DELAY
+pub fn get_retry_delay(
+    settings: &HttpSettings,
+    attempt: u32,
+    headers: Option<&HeaderMap>,
+    now_secs: u64,
+) -> Duration {
+    let server_delay = headers.and_then(|headers| {
+        get_header_secs(headers, RETRY_AFTER.as_str()).or_else(|| {
+            match get_header_secs(headers, "ratelimit-remaining") {
+                // a delay is far below the unix timestamps
+                Some(0) => get_header_secs(headers, "ratelimit-reset").map(|reset| {
+                    if reset > now_secs / 2 {
+                        reset.saturating_sub(now_secs)
+                    } else {
+                        reset
+                    }
+                }),
+                _ => None,
+            }
+        })
+    });
+    match server_delay {
+        Some(secs) => Duration::from_secs(secs),
+        None => settings
+            .retry_base_delay
+            .saturating_mul(2u32.saturating_pow(attempt)),
+    }
+    .min(RETRY_MAX_DELAY)
+}
+
+// send the request built by "build_request" (a request body can't be sent twice), retried on the
+// connection errors, the timeouts, 429 and 5xx : the last response is returned to the caller
+pub async fn send<F>(settings: &HttpSettings, build_request: F) -> anyhow::Result<Response<Body>>
+where
+    F: Fn() -> anyhow::Result<Request<Body>>,
+{
+    let client = settings.client.get(settings)?;
+    let mut attempt = 0;
+    loop {
+        let mut request = build_request()?;
+        if let Some(authorization) = settings.proxies.get_http_authorization(request.uri()) {
+            let authorization =
+                HeaderValue::from_str(authorization).context("invalid proxy authorization")?;
+            request
+                .headers_mut()
+                .insert(PROXY_AUTHORIZATION, authorization);
+        }
+        let target = format!("{} {}", request.method(), request.uri());
+        let result = match timeout(settings.read_timeout, client.request(request)).await {
+            Ok(Ok(response)) if is_retryable(response.status()) => Err((
+                anyhow!("{target} status {}", response.status()),
+                Some(response),
+            )),
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => Err((anyhow!(e).context(format!("{target} error")), None)),
+            Err(_) => Err((
+                anyhow!("{target} timeout after {:?}", settings.read_timeout),
+                None,
+            )),
+        };
+        match result {
+            Ok(response) => return Ok(response),
+            Err((e, response)) if attempt >= settings.retry_max => {
+                return response.ok_or(e);
+            }
+            Err((e, response)) => {
+                let now_secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                let headers = response.as_ref().map(|response| response.headers());
+                let delay = get_retry_delay(settings, attempt, headers, now_secs);
+                attempt += 1;
+                verbose!(
+                    "{e:#} → retry {attempt}/{} in {delay:?}",
+                    settings.retry_max
+                );
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+// the next chunk of the body, a stalled download is an error
+pub async fn next_chunk(settings: &HttpSettings, body: &mut Body) -> anyhow::Result<Option<Bytes>> {
+    match timeout(settings.read_timeout, body.data()).await {
+        Ok(chunk) => chunk.transpose().context("read body error"),
+        Err(_) => Err(anyhow!(
+            "read body timeout after {:?}",
+            settings.read_timeout
+        )),
+    }
+}
+
+pub async fn read_body(settings: &HttpSettings, response: Response<Body>) -> anyhow::Result<Bytes> {
+    let mut body = response.into_body();
+    let mut buf = Vec::new();
+    while let Some(chunk) = next_chunk(settings, &mut body).await? {
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buf))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::http_client::{get_retry_delay, read_body, send, HttpSettings, RETRY_MAX_DELAY};
+    use anyhow::Context;
+    use httptest::matchers::*;
+    use httptest::responders::{delay_and_then, status_code};
+    use httptest::{cycle, Expectation, Server};
+    use hyper::header::HeaderMap;
+    use hyper::{Body, Request, StatusCode};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // no wait between the retries of the tests
+    pub fn fast_http_settings() -> HttpSettings {
+        HttpSettings {
+            retry_base_delay: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    fn headers(values: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_get_retry_delay() {
+        let settings = HttpSettings::default();
+        let now = 1_700_000_000;
+        assert_eq!(
+            get_retry_delay(&settings, 0, None, now),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            get_retry_delay(&settings, 3, None, now),
+            Duration::from_secs(8)
+        );
+        assert_eq!(get_retry_delay(&settings, 30, None, now), RETRY_MAX_DELAY);
+        let retry_after = headers(&[("retry-after", "7")]);
+        assert_eq!(
+            get_retry_delay(&settings, 3, Some(&retry_after), now),
+            Duration::from_secs(7)
+        );
+        let gitlab_reset = headers(&[
+            ("ratelimit-remaining", "0"),
+            ("ratelimit-reset", "1700000012"),
+        ]);
+        assert_eq!(
+            get_retry_delay(&settings, 0, Some(&gitlab_reset), now),
+            Duration::from_secs(12)
+        );
+        let delta_reset = headers(&[("ratelimit-remaining", "0"), ("ratelimit-reset", "5")]);
+        assert_eq!(
+            get_retry_delay(&settings, 0, Some(&delta_reset), now),
+            Duration::from_secs(5)
+        );
+        let not_limited = headers(&[("ratelimit-remaining", "10"), ("ratelimit-reset", "5")]);
+        assert_eq!(
+            get_retry_delay(&settings, 1, Some(&not_limited), now),
+            Duration::from_secs(2)
+        );
+        let too_long = headers(&[("retry-after", "3600")]);
+        assert_eq!(
+            get_retry_delay(&settings, 0, Some(&too_long), now),
+            RETRY_MAX_DELAY
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_retry() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/retry"))
+                .times(3)
+                .respond_with(cycle![
+                    status_code(502),
+                    status_code(429).insert_header("Retry-After", "0"),
+                    status_code(200).body("ok"),
+                ]),
+        );
+        let url = server.url("/retry").to_string();
+        let settings = fast_http_settings();
+        let response = send(&settings, || {
+            Request::get(&url)
+                .body(Body::empty())
+                .context("request error")
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(read_body(&settings, response).await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_shared_client() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ok"))
+                .times(2)
+                .respond_with(status_code(200)),
+        );
+        let url = server.url("/ok").to_string();
+        let settings = fast_http_settings();
+        let request = || {
+            Request::get(&url)
+                .body(Body::empty())
+                .context("request error")
+        };
+        // the client built by the request of a clone is the client of the settings
+        send(&settings.clone(), request).await.unwrap();
+        assert!(settings.client.0.get().is_some());
+        let settings_clone = settings.clone();
+        send(&settings_clone, request).await.unwrap();
+        assert!(Arc::ptr_eq(&settings.client.0, &settings_clone.client.0));
+    }
+
+    #[tokio::test]
+    async fn test_send_retry_max() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/down"))
+                .times(2)
+                .respond_with(status_code(503)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/not-found"))
+                .times(1)
+                .respond_with(status_code(404)),
+        );
+        let settings = HttpSettings {
+            retry_max: 1,
+            ..fast_http_settings()
+        };
+        for (path, status) in [
+            ("/down", StatusCode::SERVICE_UNAVAILABLE),
+            ("/not-found", StatusCode::NOT_FOUND),
+        ] {
+            let url = server.url(path).to_string();
+            let response = send(&settings, || {
+                Request::get(&url)
+                    .body(Body::empty())
+                    .context("request error")
+            })
+            .await
+            .unwrap();
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_timeout() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/slow"))
+                .times(2)
+                .respond_with(delay_and_then(Duration::from_millis(500), status_code(200))),
+        );
+        let url = server.url("/slow").to_string();
+        let settings = HttpSettings {
+            read_timeout: Duration::from_millis(50),
+            retry_max: 1,
+            ..fast_http_settings()
+        };
+        let err = send(&settings, || {
+            Request::get(&url)
+                .body(Body::empty())
+                .context("request error")
+        })
+        .await
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("/slow timeout after 50ms"));
+    }
+
+    #[tokio::test]
+    async fn test_send_connect_error() {
+        let settings = HttpSettings {
+            retry_max: 0,
+            ..fast_http_settings()
+        };
+        let err = send(&settings, || {
+            Request::get("http://127.0.0.1:1/")
+                .body(Body::empty())
+                .context("request error")
+        })
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(err.to_string(), "GET http://127.0.0.1:1/ error");
+    }
+}
@@ -0,0 +1,131 @@
+use regex::Regex;
+use std::fmt::{Display, Formatter};
+
+// SKIP_CI_JOB_NAME_MATCH : how the names of the jobs are compared with CI_JOB_NAME
+#[derive(Debug, Clone, Default)]
+pub enum JobNameMatch {
+    // the same name : "test 1/3" reuses "test 1/3", "build: [linux, amd64]" the same tuple
+    #[default]
+    Exact,
+    // the same name without the index of parallel : "test 1/3" reuses "test 2/3" and "test 1/4"
+    Parallel,
+    // the same name and the same values of the parallel:matrix tuple, whatever their spacing
+    Matrix,
+    // SKIP_CI_JOB_NAME_REGEX : the jobs whose name matches the regex
+    Regex(Regex),
+}
+
+impl PartialEq for JobNameMatch {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (JobNameMatch::Regex(a), JobNameMatch::Regex(b)) => a.as_str() == b.as_str(),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Display for JobNameMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobNameMatch::Exact => write!(f, "exact"),
+            JobNameMatch::Parallel => write!(f, "parallel"),
+            JobNameMatch::Matrix => write!(f, "matrix"),
+            JobNameMatch::Regex(regex) => write!(f, "regex {}", regex.as_str()),
+        }
+    }
+}
+
+impl JobNameMatch {
+    pub fn matches(&self, job_name: &str, ci_job_name: &str) -> bool {
+        match self {
+            JobNameMatch::Exact => job_name == ci_job_name,
+            JobNameMatch::Parallel => {
+                strip_parallel_index(job_name) == strip_parallel_index(ci_job_name)
+            }
+            JobNameMatch::Matrix => split_matrix(job_name) == split_matrix(ci_job_name),
+            JobNameMatch::Regex(regex) => regex.is_match(job_name),
+        }
+    }
+}
+
+// "test 1/3" → "test"
+pub fn strip_parallel_index(job_name: &str) -> &str {
+    let Some((name, index)) = job_name.rsplit_once(' ') else {
+        return job_name;
+    };
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    match index.split_once('/') {
+        Some((n, total)) if is_number(n) && is_number(total) => name.trim_end(),
+        _ => job_name,
+    }
+}
+
+// "build: [linux, amd64]" → ("build", ["linux", "amd64"]), the name only without a tuple
+pub fn split_matrix(job_name: &str) -> (&str, Vec<&str>) {
+    let tuple = job_name
+        .strip_suffix(']')
+        .and_then(|name| name.rsplit_once('['))
+        .and_then(|(name, values)| Some((name.trim_end().strip_suffix(':')?, values)));
+    match tuple {
+        Some((name, values)) => (name.trim_end(), values.split(',').map(str::trim).collect()),
+        None => (job_name.trim(), Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::job_name::{split_matrix, strip_parallel_index, JobNameMatch};
+    use regex::Regex;
+
+    #[test]
+    fn test_strip_parallel_index() {
+        assert_eq!(strip_parallel_index("test 1/3"), "test");
+        assert_eq!(strip_parallel_index("unit test 12/20"), "unit test");
+        assert_eq!(strip_parallel_index("test"), "test");
+        assert_eq!(strip_parallel_index("test a/3"), "test a/3");
+        assert_eq!(strip_parallel_index("test 1/"), "test 1/");
+        assert_eq!(strip_parallel_index("deploy src/main"), "deploy src/main");
+    }
+
+    #[test]
+    fn test_split_matrix() {
+        assert_eq!(
+            split_matrix("build: [linux, amd64]"),
+            ("build", vec!["linux", "amd64"])
+        );
+        assert_eq!(
+            split_matrix("build:[linux,amd64]"),
+            ("build", vec!["linux", "amd64"])
+        );
+        assert_eq!(split_matrix("build"), ("build", vec![]));
+        assert_eq!(split_matrix("build [linux]"), ("build [linux]", vec![]));
+    }
+
+    #[test]
+    fn test_job_name_match() {
+        let exact = JobNameMatch::Exact;
+        assert!(exact.matches("test 1/3", "test 1/3"));
+        assert!(!exact.matches("test 2/3", "test 1/3"));
+
+        let parallel = JobNameMatch::Parallel;
+        assert!(parallel.matches("test 2/3", "test 1/3"));
+        assert!(parallel.matches("test 1/4", "test 1/3"));
+        assert!(parallel.matches("test", "test 1/3"));
+        assert!(parallel.matches("test", "test"));
+        assert!(!parallel.matches("lint 1/3", "test 1/3"));
+        assert!(!parallel.matches("build: [linux, arm64]", "build: [linux, amd64]"));
+
+        let matrix = JobNameMatch::Matrix;
+        assert!(matrix.matches("build: [linux, amd64]", "build: [linux, amd64]"));
+        assert!(matrix.matches("build: [linux,amd64]", "build: [linux, amd64]"));
+        assert!(!matrix.matches("build: [linux, arm64]", "build: [linux, amd64]"));
+        assert!(!matrix.matches("build: [amd64, linux]", "build: [linux, amd64]"));
+        assert!(!matrix.matches("test: [linux, amd64]", "build: [linux, amd64]"));
+        assert!(!matrix.matches("build", "build: [linux, amd64]"));
+        assert!(matrix.matches("build", "build"));
+
+        let regex = JobNameMatch::Regex(Regex::new(r"^build: \[linux, .*\]$").unwrap());
+        assert!(regex.matches("build: [linux, arm64]", "build: [linux, amd64]"));
+        assert!(!regex.matches("build: [macos, arm64]", "build: [linux, amd64]"));
+    }
+}
@@ -1,6 +1,13 @@
+use crate::auth::Credential;
+use crate::config::Config;
+use crate::http_client::{read_body, send, HttpSettings};
 use crate::verbose;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
+use hyper::header::HeaderMap;
+use hyper::{Body, Request};
 use serde::Deserialize;
+use std::collections::VecDeque;
+use tokio::task::JoinHandle;
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct GitlabJob {
@@ -23,42 +30,323 @@ pub fn deserialize_jobs(jobs_json: &str) -> anyhow::Result<Vec<GitlabJob>> {
     serde_json::from_str(jobs_json).context("deserialize jobs error")
 }
 
-pub async fn get_project_jobs(
-    project_jobs_api_url: &str,
-    page_num: u32,
-    private_token: &str,
-) -> anyhow::Result<Vec<GitlabJob>> {
-    let url = format!(
-        "{project_jobs_api_url}?scope=success&per_page=100&page={page_num}&private_token={private_token}",
-    );
-    verbose!("GET {url}");
+const JOBS_PER_PAGE: usize = 100;
 
-    let https = hyper_rustls::HttpsConnectorBuilder::new()
-        .with_webpki_roots()
-        .https_or_http()
-        .enable_http1()
-        .build();
-    let client: hyper::Client<_, hyper::Body> = hyper::Client::builder().build(https);
+// SKIP_CI_PAGINATION
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Pagination {
+    // page=N : the Gitlab versions without the keyset pagination of the jobs
+    #[default]
+    Offset,
+    // pagination=keyset : the cost of a page doesn't grow with its offset
+    Keyset,
+}
 
-    let response = client
-        .get(url.parse().context("parse url error")?)
-        .await
-        .context("Error while request the jobs")?;
-    let buf = hyper::body::to_bytes(response)
+fn get_first_page_url(project_jobs_api_url: &str, pagination: Pagination) -> String {
+    match pagination {
+        Pagination::Offset => {
+            format!("{project_jobs_api_url}?scope=success&per_page={JOBS_PER_PAGE}&page=1")
+        }
+        Pagination::Keyset => format!(
+            "{project_jobs_api_url}?scope=success&per_page={JOBS_PER_PAGE}\
+            &pagination=keyset&order_by=id&sort=desc"
+        ),
+    }
+}
+
+// the url of the following page of the offset pagination : the "page" parameter of "url" + 1,
+// None without "page" parameter
+fn get_following_page_url(url: &str) -> Option<String> {
+    let (path, query) = url.split_once('?')?;
+    let mut found = false;
+    let params = query
+        .split('&')
+        .map(
+            |param| match param.strip_prefix("page=").map(str::parse::<u32>) {
+                Some(Ok(page_num)) if !found => {
+                    found = true;
+                    format!("page={}", page_num + 1)
+                }
+                _ => param.to_string(),
+            },
+        )
+        .collect::<Vec<String>>();
+    found.then(|| format!("{path}?{}", params.join("&")))
+}
+
+// the same url whatever the order of the query parameters
+fn is_same_url(url: &str, other: &str) -> bool {
+    fn split(url: &str) -> (&str, Vec<&str>) {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let mut params = query.split('&').collect::<Vec<&str>>();
+        params.sort_unstable();
+        (path, params)
+    }
+    split(url) == split(other)
+}
+
+// the url of the rel="next" link : <https://...&page=2>; rel="next", <https://...>; rel="first"
+fn get_link_next(link: &str) -> Option<String> {
+    link.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim().replace(' ', "") == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+// the Link header (offset and keyset), then the X-Next-Page header (offset, empty on the last
+// page), then the next offset if the page is full (a server without the pagination headers)
+fn get_next_page_url(
+    headers: &HeaderMap,
+    jobs_count: usize,
+    next_offset_page_url: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(link) = header("link") {
+        return get_link_next(link);
+    }
+    match header("x-next-page") {
+        Some(next_page) if next_page.trim().is_empty() => None,
+        Some(_) => next_offset_page_url(),
+        None if jobs_count >= JOBS_PER_PAGE => next_offset_page_url(),
+        None => None,
+    }
+}
+
+type JobsPageResult = anyhow::Result<(Vec<GitlabJob>, HeaderMap)>;
+
+// the pages of the successful jobs of the project, the most recent first : stop at the last page
+// or after SKIP_CI_PAGE_TO_FETCH_MAX pages. The urls of the offset pagination are guessed from
+// the url of the next page given by the server : the next SKIP_CI_PAGE_PREFETCH_MAX pages are
+// downloaded while the current page is checked, the pages are still returned in order
+pub struct JobPages {
+    pagination: Pagination,
+    next_url: Option<String>,
+    credential: Credential,
+    http_settings: HttpSettings,
+    page_to_fetch_max: u32,
+    page_prefetch_max: u32,
+    // the urls and the requests of the next page and of the prefetched pages
+    requested_pages: VecDeque<(String, JoinHandle<JobsPageResult>)>,
+    pub page_num: u32,
+}
+
+impl JobPages {
+    pub fn new(config: &Config) -> JobPages {
+        JobPages {
+            pagination: config.pagination,
+            next_url: Some(get_first_page_url(&config.jobs_api_url, config.pagination)),
+            credential: config.api_credential(),
+            http_settings: config.http_settings(),
+            page_to_fetch_max: config.page_to_fetch_max,
+            page_prefetch_max: config.page_prefetch_max,
+            requested_pages: VecDeque::new(),
+            page_num: 0,
+        }
+    }
+
+    fn request_page(&mut self, url: String) {
+        let credential = self.credential.clone();
+        let http_settings = self.http_settings.clone();
+        let request_url = url.clone();
+        let request = tokio::spawn(async move {
+            get_project_jobs(&request_url, &credential, &http_settings).await
+        });
+        self.requested_pages.push_back((url, request));
+    }
+
+    // request the next page "url" if it isn't prefetched, then the prefetched pages that are not
+    // requested yet
+    fn request_pages(&mut self, url: &str) {
+        match self.requested_pages.front() {
+            Some((requested_url, _)) if is_same_url(requested_url, url) => {}
+            requested_page => {
+                if requested_page.is_some() {
+                    verbose!("the prefetched pages don't follow {url} : aborted");
+                }
+                self.abort_requested_pages();
+                self.request_page(url.to_string());
+            }
+        }
+        let page_count = (1 + self.page_prefetch_max).min(self.page_to_fetch_max - self.page_num);
+        while self.requested_pages.len() < page_count as usize {
+            let Some(url) = self
+                .requested_pages
+                .back()
+                .and_then(|(url, _)| get_following_page_url(url))
+            else {
+                break;
+            };
+            verbose!("prefetch {url}");
+            self.request_page(url);
+        }
+    }
+
+    // the jobs of the next page, None after the last page
+    pub async fn next(&mut self) -> anyhow::Result<Option<Vec<GitlabJob>>> {
+        if self.page_num >= self.page_to_fetch_max {
+            verbose!("page_to_fetch_max reached : {}", self.page_to_fetch_max);
+            return Ok(None);
+        }
+        let Some(url) = self.next_url.take() else {
+            verbose!("no more pages after the page {}", self.page_num);
+            return Ok(None);
+        };
+        let (jobs, headers) = match self.pagination {
+            // the url of the next page is in the Link header of the current page
+            Pagination::Keyset => {
+                get_project_jobs(&url, &self.credential, &self.http_settings).await?
+            }
+            // the url of the next page is in the Link or X-Next-Page headers of the current page,
+            // the following pages are guessed
+            Pagination::Offset => {
+                self.request_pages(&url);
+                self.requested_pages
+                    .pop_front()
+                    .context("no page requested")?
+                    .1
+                    .await
+                    .context("Error while request the jobs")??
+            }
+        };
+        self.page_num += 1;
+        self.next_url = get_next_page_url(&headers, jobs.len(), || get_following_page_url(&url));
+        if self.next_url.is_none() {
+            self.abort_requested_pages();
+        }
+        Ok(Some(jobs))
+    }
+
+    // the prefetched pages after the last page, or not needed anymore
+    fn abort_requested_pages(&mut self) {
+        for (_, request) in self.requested_pages.drain(..) {
+            request.abort();
+        }
+    }
+}
+
+impl Drop for JobPages {
+    fn drop(&mut self) {
+        self.abort_requested_pages();
+    }
+}
+
+// the jobs of a page and the headers of the response (the pagination)
+async fn get_project_jobs(
+    url: &str,
+    credential: &Credential,
+    http_settings: &HttpSettings,
+) -> anyhow::Result<(Vec<GitlabJob>, HeaderMap)> {
+    verbose!("GET {url}");
+    let response = send(http_settings, || {
+        credential
+            .authorize(Request::get(url))
+            .body(Body::empty())
+            .context("parse url error")
+    })
+    .await
+    .context("Error while request the jobs")?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let buf = read_body(http_settings, response)
         .await
         .context("Error while extract jobs body")?;
     let body_str = String::from_utf8(buf.to_vec()).context("buffer to String error")?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Error while request the jobs {status} : {body_str}"
+        ));
+    }
     let jobs = deserialize_jobs(&body_str).context("Error while deserialize jobs")?;
     verbose!(" → {} jobs fetched", jobs.len());
+    Ok((jobs, headers))
+}
+
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct GitlabPipeline {
+    pub id: u32,
+}
+
+// the url of the project api : the parent of the jobs url
+fn get_project_api_url(config: &Config) -> &str {
+    config.jobs_api_url.trim_end_matches("/jobs")
+}
+
+// the ids of the pipelines of a commit, the most recent first
+pub async fn get_pipeline_ids_of_commit(config: &Config, sha: &str) -> anyhow::Result<Vec<u32>> {
+    let url = format!(
+        "{}/pipelines?sha={sha}&per_page={JOBS_PER_PAGE}",
+        get_project_api_url(config)
+    );
+    verbose!("GET {url}");
+    let http_settings = config.http_settings();
+    let credential = config.api_credential();
+    let response = send(&http_settings, || {
+        credential
+            .authorize(Request::get(&url))
+            .body(Body::empty())
+            .context("parse url error")
+    })
+    .await
+    .context("Error while request the pipelines")?;
+    let status = response.status();
+    let buf = read_body(&http_settings, response)
+        .await
+        .context("Error while extract pipelines body")?;
+    let body_str = String::from_utf8(buf.to_vec()).context("buffer to String error")?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Error while request the pipelines {status} : {body_str}"
+        ));
+    }
+    let pipelines = serde_json::from_str::<Vec<GitlabPipeline>>(&body_str)
+        .context("Error while deserialize pipelines")?;
+    verbose!(" → {} pipelines fetched", pipelines.len());
+    Ok(pipelines.into_iter().map(|pipeline| pipeline.id).collect())
+}
+
+// the successful jobs of a pipeline, all its pages
+pub async fn get_pipeline_jobs(
+    config: &Config,
+    pipeline_id: u32,
+) -> anyhow::Result<Vec<GitlabJob>> {
+    let credential = config.api_credential();
+    let http_settings = config.http_settings();
+    let mut next_url = Some(format!(
+        "{}/pipelines/{pipeline_id}/jobs?scope=success&per_page={JOBS_PER_PAGE}&page=1",
+        get_project_api_url(config)
+    ));
+    let mut jobs = Vec::new();
+    while let Some(url) = next_url.take() {
+        let (page_jobs, headers) = get_project_jobs(&url, &credential, &http_settings).await?;
+        next_url = get_next_page_url(&headers, page_jobs.len(), || get_following_page_url(&url));
+        jobs.extend(page_jobs);
+    }
     Ok(jobs)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::jobs::{deserialize_jobs, get_project_jobs, GitlabCommit, GitlabJob};
-    use httptest::matchers::request;
-    use httptest::responders::status_code;
-    use httptest::{Expectation, Server};
+    use crate::auth::Credential;
+    use crate::config::Config;
+    use crate::http_client::tests::fast_http_settings;
+    use crate::jobs::{
+        deserialize_jobs, get_following_page_url, get_link_next, get_next_page_url,
+        get_pipeline_jobs, get_project_jobs, is_same_url, GitlabCommit, GitlabJob, JobPages,
+        Pagination,
+    };
+    use httptest::matchers::*;
+    use httptest::responders::{delay_and_then, status_code};
+    use httptest::{all_of, Expectation, Server};
+    use hyper::header::HeaderMap;
+    use std::time::{Duration, Instant};
     #[test]
     fn test_deserialize_jobs() {
         let expected_job = GitlabJob {
@@ -86,7 +374,7 @@ mod tests {
                 ]"###,
         );
 
-        assert!(expected_job.eq(job_res.unwrap().get(0).unwrap()));
+        assert!(expected_job.eq(job_res.unwrap().first().unwrap()));
     }
 
     #[tokio::test]
@@ -94,9 +382,13 @@ mod tests {
         let server = Server::run();
 
         server.expect(
-            Expectation::matching(request::method_path("GET", "/api/123/jobs/456")).respond_with(
-                status_code(200).body(
-                    r###"[
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/jobs/456"),
+                request::headers(contains(("private-token", "__PRIVATE_TOKEN__"))),
+                request::query(url_decoded(not(contains(key("private_token"))))),
+            ])
+            .respond_with(status_code(200).body(
+                r###"[
   {
     "artifacts_expire_at": "2023-03-12T19:59:33.250Z",
     "commit": {
@@ -120,11 +412,11 @@ mod tests {
     "web_url": "https://gitlab.localhost/skip/skip-rs/-/jobs/12345679"
   }
 ]"###,
-                ),
-            ),
+            )),
         );
         let url = server.url_str("/api/123/jobs/456");
-        let jobs = get_project_jobs(&url, 1, "__PRIVATE_TOKEN__")
+        let credential = Credential::PrivateToken("__PRIVATE_TOKEN__".to_string());
+        let (jobs, _) = get_project_jobs(&url, &credential, &fast_http_settings())
             .await
             .unwrap();
         let expected_jobs = vec![
@@ -157,14 +449,293 @@ mod tests {
     #[tokio::test]
     async fn test_get_project_jobs_server_down() {
         let url = ("/api/123/jobs/456").to_string();
-        let error = get_project_jobs(&url, 1, "")
+        let error = get_project_jobs(&url, &Credential::None, &fast_http_settings())
             .await
             .err()
             .map(|e| format!("{e:#}"))
             .unwrap();
         assert_eq!(
             error,
-            "Error while request the jobs: client requires absolute-form URIs"
+            "Error while request the jobs: GET /api/123/jobs/456 error: client requires absolute-form URIs"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_project_jobs_unauthorized() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/123/jobs"))
+                .respond_with(status_code(401).body(r#"{"message":"401 Unauthorized"}"#)),
+        );
+        let url = server.url_str("/api/123/jobs");
+        let error = get_project_jobs(&url, &Credential::None, &fast_http_settings())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            r#"Error while request the jobs 401 Unauthorized : {"message":"401 Unauthorized"}"#
+        );
+    }
+
+    // a page of "count" jobs of jobA, the ids from "first_id"
+    fn jobs_json(first_id: u32, count: u32) -> String {
+        let jobs = (first_id..first_id + count)
+            .map(|id| {
+                format!(
+                    r#"{{"artifacts_expire_at":null,"commit":{{"id":"{id:040}"}},"id":{id},
+                    "name":"jobA","ref":"main","status":"success","web_url":""}}"#
+                )
+            })
+            .collect::<Vec<String>>();
+        format!("[{}]", jobs.join(","))
+    }
+
+    #[test]
+    fn test_get_link_next() {
+        assert_eq!(
+            get_link_next(
+                r#"<https://gitlab.example.com/api/v4/projects/8/jobs?page=2&per_page=100>; rel="next", <https://gitlab.example.com/api/v4/projects/8/jobs?page=1&per_page=100>; rel="first""#
+            ),
+            Some(
+                "https://gitlab.example.com/api/v4/projects/8/jobs?page=2&per_page=100".to_string()
+            )
+        );
+        assert_eq!(
+            get_link_next(
+                r#"<https://gitlab.example.com/api/v4/projects/8/jobs?page=1>; rel="first""#
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_following_page_url() {
+        assert_eq!(
+            get_following_page_url("https://gitlab/jobs?per_page=100&page=9&scope=success"),
+            Some("https://gitlab/jobs?per_page=100&page=10&scope=success".to_string())
+        );
+        assert_eq!(
+            get_following_page_url("https://gitlab/jobs?per_page=100"),
+            None
+        );
+        assert_eq!(get_following_page_url("https://gitlab/jobs"), None);
+        assert!(is_same_url(
+            "https://gitlab/jobs?page=2&per_page=100",
+            "https://gitlab/jobs?per_page=100&page=2"
+        ));
+        assert!(!is_same_url(
+            "https://gitlab/jobs?page=2&per_page=100",
+            "https://gitlab/jobs?page=3&per_page=100"
+        ));
+    }
+
+    #[test]
+    fn test_get_next_page_url() {
+        let headers = |values: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in values {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        let next = || Some("next-offset".to_string());
+        let link = headers(&[
+            ("link", r#"<https://next>; rel="next""#),
+            ("x-next-page", "2"),
+        ]);
+        assert_eq!(
+            get_next_page_url(&link, 100, next),
+            Some("https://next".to_string())
+        );
+        let last_link = headers(&[("link", r#"<https://first>; rel="first""#)]);
+        assert_eq!(get_next_page_url(&last_link, 100, next), None);
+        let next_page = headers(&[("x-next-page", "2")]);
+        assert_eq!(
+            get_next_page_url(&next_page, 5, next),
+            Some("next-offset".to_string())
+        );
+        let last_page = headers(&[("x-next-page", "")]);
+        assert_eq!(get_next_page_url(&last_page, 100, next), None);
+        assert_eq!(
+            get_next_page_url(&HeaderMap::new(), 100, next),
+            Some("next-offset".to_string())
         );
+        assert_eq!(get_next_page_url(&HeaderMap::new(), 99, next), None);
+    }
+
+    #[tokio::test]
+    async fn test_job_pages_offset() {
+        let server = Server::run();
+        for (page, next_page, first_id) in [("1", "2", 1), ("2", "", 101)] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/api/123/jobs"),
+                    request::query(url_decoded(contains(("page", page)))),
+                ])
+                .times(1)
+                .respond_with(
+                    status_code(200)
+                        .insert_header("x-next-page", next_page)
+                        .body(jobs_json(first_id, 100)),
+                ),
+            );
+        }
+        let config = Config {
+            jobs_api_url: server.url_str("/api/123/jobs"),
+            page_to_fetch_max: 5,
+            page_prefetch_max: 0,
+            ..Default::default()
+        };
+        let mut pages = JobPages::new(&config);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 1);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 101);
+        assert_eq!(pages.page_num, 2);
+        // X-Next-Page is empty : no third request
+        assert_eq!(pages.next().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_job_pages_prefetch() {
+        let server = Server::run();
+        let delay = Duration::from_millis(300);
+        for (page, next_page, first_id, times) in [
+            ("1", "2", 1, 1..=1),
+            ("2", "3", 101, 1..=1),
+            ("3", "", 201, 1..=1),
+            // requested with the page 3, before its X-Next-Page is known
+            ("4", "", 301, 0..=1),
+        ] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/api/123/jobs"),
+                    request::query(url_decoded(contains(("page", page)))),
+                ])
+                .times(times)
+                .respond_with(delay_and_then(
+                    delay,
+                    status_code(200)
+                        .insert_header("x-next-page", next_page)
+                        .body(jobs_json(first_id, 100)),
+                )),
+            );
+        }
+        let config = Config {
+            jobs_api_url: server.url_str("/api/123/jobs"),
+            page_to_fetch_max: 5,
+            page_prefetch_max: 2,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut pages = JobPages::new(&config);
+        // the pages keep their order
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 1);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 101);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 201);
+        assert_eq!(pages.page_num, 3);
+        assert_eq!(pages.next().await.unwrap(), None);
+        // the pages 1 to 3 are downloaded at the same time
+        assert!(start.elapsed() < delay * 2, "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_job_pages_keyset() {
+        let server = Server::run();
+        let next_url = server.url_str("/api/123/jobs?cursor=eyJpZCI6IjEwMCJ9");
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/jobs"),
+                request::query(url_decoded(contains(("pagination", "keyset")))),
+                request::query(url_decoded(contains(("order_by", "id")))),
+            ])
+            .times(1)
+            .respond_with(
+                status_code(200)
+                    .insert_header("link", format!(r#"<{next_url}>; rel="next""#))
+                    .body(jobs_json(1, 100)),
+            ),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/api/123/jobs"),
+                request::query(url_decoded(contains(("cursor", "eyJpZCI6IjEwMCJ9")))),
+            ])
+            .times(1)
+            .respond_with(status_code(200).body(jobs_json(101, 100))),
+        );
+        let config = Config {
+            jobs_api_url: server.url_str("/api/123/jobs"),
+            pagination: Pagination::Keyset,
+            page_to_fetch_max: 2,
+            ..Default::default()
+        };
+        let mut pages = JobPages::new(&config);
+        assert_eq!(pages.next().await.unwrap().unwrap().len(), 100);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 101);
+        // page_to_fetch_max is reached
+        assert_eq!(pages.next().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_job_pages_offset_link() {
+        let server = Server::run();
+        let next_url = server.url_str("/api/123/jobs?page=5&per_page=100&scope=success");
+        for (page, link, first_id, times) in [
+            ("1", format!(r#"<{next_url}>; rel="next""#), 1, 1..=1),
+            // guessed before the Link header of the page 1 is known
+            ("2", String::new(), 101, 0..=1),
+            ("5", String::new(), 401, 1..=1),
+            ("6", String::new(), 501, 0..=1),
+        ] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/api/123/jobs"),
+                    request::query(url_decoded(contains(("page", page)))),
+                ])
+                .times(times)
+                .respond_with(
+                    status_code(200)
+                        .insert_header("link", link)
+                        .body(jobs_json(first_id, 100)),
+                ),
+            );
+        }
+        let config = Config {
+            jobs_api_url: server.url_str("/api/123/jobs"),
+            page_to_fetch_max: 5,
+            page_prefetch_max: 1,
+            ..Default::default()
+        };
+        let mut pages = JobPages::new(&config);
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 1);
+        // the server url is followed, the guessed page 2 is aborted
+        assert_eq!(pages.next().await.unwrap().unwrap()[0].id, 401);
+        assert_eq!(pages.next().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_pipeline_jobs() {
+        let server = Server::run();
+        for (page, next_page, first_id, count) in [("1", "2", 1, 100), ("2", "", 101, 20)] {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path("GET", "/api/123/pipelines/7/jobs"),
+                    request::query(url_decoded(contains(("page", page)))),
+                ])
+                .times(1)
+                .respond_with(
+                    status_code(200)
+                        .insert_header("x-next-page", next_page)
+                        .body(jobs_json(first_id, count)),
+                ),
+            );
+        }
+        let config = Config {
+            jobs_api_url: server.url_str("/api/123/jobs"),
+            ..Default::default()
+        };
+        let jobs = get_pipeline_jobs(&config, 7).await.unwrap();
+        assert_eq!(jobs.len(), 120);
+        assert_eq!(jobs[100].id, 101);
     }
 }
@@ -1,24 +1,62 @@
+mod ancestors;
 mod artifact;
+mod auth;
+mod cli;
 mod config;
+mod config_file;
+mod doctor;
+mod explain;
 mod find_last_job_ok;
+mod fingerprint;
+mod fs_store;
 mod git;
+mod git_notes;
+mod glob;
 mod help;
+mod history;
+mod http_client;
+mod job_name;
 mod jobs;
 mod process;
+mod proxy;
+mod record;
+mod registry_store;
+mod s3_store;
+mod sigv4;
 mod skip_ci_file;
 mod skipci_log;
+mod tls;
 mod trace;
 
 #[cfg(not(tarpaulin_include))]
-#[tokio::main(flavor = "current_thread")]
+// a second worker downloads the prefetched pages of jobs while the trees are compared
+#[tokio::main(flavor = "multi_thread", worker_threads = 2)]
 async fn main() {
-    if std::env::args().len() <= 1 {
-        verbose!("{}", help::get_version_msg());
-        let config = config::config_from_env();
-        let exit_code = process::process_with_exit_code(config).await;
-        std::process::exit(exit_code);
-    } else {
-        help::print_help();
-        std::process::exit(5);
-    }
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let cli_args = match cli::parse_args(&args) {
+        Ok(cli_args) => cli_args,
+        Err(e) => {
+            skipci_log::red(&format!("❌ {e}"));
+            help::print_help();
+            std::process::exit(5);
+        }
+    };
+    verbose!("{}", help::get_version_msg());
+    let config = || config::config_from_env(&cli_args);
+    let exit_code = match cli_args.command {
+        cli::Command::Check => process::process_with_exit_code(config()).await,
+        cli::Command::Fingerprint => fingerprint::fingerprint_with_exit_code(
+            config(),
+            cli_args.commit.as_deref(),
+            cli_args.format,
+        ),
+        cli::Command::Explain => explain::explain_with_exit_code(config()).await,
+        cli::Command::Record => record::record_with_exit_code(config()).await,
+        cli::Command::Doctor => doctor::doctor_with_exit_code(config()).await,
+        cli::Command::Help => {
+            help::print_help();
+            5
+        }
+    };
+    std::process::exit(exit_code);
 }
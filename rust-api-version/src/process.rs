@@ -1,8 +1,13 @@
 use crate::artifact::extract_artifacts;
-use crate::config::Config;
-use crate::find_last_job_ok::find_last_job_ok;
+use crate::config::{Backend, Config};
+use crate::find_last_job_ok::{find_last_job_ok, get_head_fingerprint};
+use crate::history::{
+    extract_artifacts_of_record, find_in_history, record_in_history, HistoryRecord,
+};
 use crate::jobs::GitlabJob;
-use crate::process::ProcessResult::{JobFound, JobNotFound, Skip, SkipCiFileExists};
+use crate::process::ProcessResult::{
+    HistoryFound, JobFound, JobNotFound, Skip, SkipCiFileExists, SkipCiValue,
+};
 use crate::skip_ci_file::{check_skip_is_done, write_skip_done};
 use crate::skipci_log::{green, red, yellow};
 use crate::trace::{
@@ -16,8 +21,98 @@ use tokio::time::Instant;
 pub enum ProcessResult {
     SkipCiFileExists(bool),
     JobFound(GitlabJob, String),
+    HistoryFound(HistoryRecord),
     JobNotFound,
     Skip,
+    SkipCiValue(bool),
+}
+
+// search the fingerprint of the HEAD in the last jobs of the project, from the Gitlab API
+async fn process_api(config: &Config) -> anyhow::Result<ProcessResult> {
+    // 3. Get last successful jobs of the project
+    let job_ok = find_last_job_ok(config).await?;
+
+    // extract job artifact
+    let process_result = match job_ok {
+        Some(job) => {
+            if config.dry_run {
+                verbose!("SKIP_CI_DRY_RUN=true → skip the artifact download");
+            } else if config.no_artifact {
+                verbose!("SKIP_CI_NO_ARTIFACT=true → skip the artifact download");
+            } else {
+                extract_artifacts(config, &job).await?;
+            }
+            let oldest_ancestor = if config.dry_run {
+                // no marker in the trace : a later job must not inherit the job found by a dry run
+                verbose!("SKIP_CI_DRY_RUN=true → skip the oldest ancestor of the trace");
+                job.web_url.clone()
+            } else {
+                let trace_url = get_trace_url(&config.jobs_api_url, job.id);
+                let oldest_ancestor = match parse_oldest_ancestor_from_job_trace(
+                    &trace_url,
+                    &config.api_credential(),
+                    &config.http_settings(),
+                )
+                .await
+                {
+                    Ok(Some(url)) => url,
+                    _ => job.web_url.clone(),
+                };
+
+                // Important to keep for the futur job that will parse this trace
+                println!("{SKIP_CI_OLDEST_ANCESTOR_KEY}={oldest_ancestor}");
+                oldest_ancestor
+            };
+
+            JobFound(job, oldest_ancestor)
+        }
+        None => JobNotFound,
+    };
+    Ok(process_result)
+}
+
+// search the fingerprint of the HEAD in the history store of the backend
+async fn process_history(
+    config: &Config,
+    record_if_not_found: bool,
+) -> anyhow::Result<ProcessResult> {
+    let fingerprint = get_head_fingerprint(config)?;
+
+    let process_result = match find_in_history(config, &fingerprint).await? {
+        Some(record) => {
+            if config.dry_run {
+                verbose!("SKIP_CI_DRY_RUN=true → skip the artifact download");
+            } else if config.no_artifact {
+                verbose!("SKIP_CI_NO_ARTIFACT=true → skip the artifact download");
+            } else {
+                extract_artifacts_of_record(config, &record).await?;
+            }
+            HistoryFound(record)
+        }
+        None => {
+            if record_if_not_found && !config.dry_run && config.backend == Backend::Cache {
+                // like the bash/go/node cache versions : the cache is saved if the job succeeds
+                let record = HistoryRecord {
+                    fingerprint,
+                    job_id: config.ci_job_id,
+                    job_ref: None,
+                    timestamp: None,
+                };
+                record_in_history(config, &record).await?;
+            }
+            JobNotFound
+        }
+    };
+    Ok(process_result)
+}
+
+// search the fingerprint of the HEAD with the backend, the cache backend records it if it is not
+// found and record_if_not_found is true
+pub async fn search(config: &Config, record_if_not_found: bool) -> anyhow::Result<ProcessResult> {
+    match config.backend {
+        Backend::Api => process_api(config).await,
+        _ => process_history(config, record_if_not_found).await,
+    }
 }
 
 async fn process(config: &Config) -> anyhow::Result<ProcessResult> {
@@ -26,41 +121,28 @@ async fn process(config: &Config) -> anyhow::Result<ProcessResult> {
         // If file exists, exit
         Some(skip_ci) => SkipCiFileExists(skip_ci),
         None => {
-            let process_result;
-            if config.skip {
-                process_result = Skip;
+            let process_result = if config.skip {
+                Skip
+            } else if let Some(skip_ci_value) = config.skip_ci_value {
+                SkipCiValue(skip_ci_value)
             } else {
-                // 3. Get last successful jobs of the project
-                let job_ok = find_last_job_ok(config).await?;
-
-                // extract job artifact
-                process_result = match job_ok {
-                    Some(job) => {
-                        extract_artifacts(config, &job).await?;
-                        let trace_url =
-                            get_trace_url(&config.jobs_api_url, job.id, &config.api_read_token);
-                        let oldest_ancestor =
-                            match parse_oldest_ancestor_from_job_trace(&trace_url).await {
-                                Ok(Some(url)) => url,
-                                _ => job.web_url.clone(),
-                            };
-
-                        // Important to keep for the futur job that will parse this trace
-                        println!("{SKIP_CI_OLDEST_ANCESTOR_KEY}={oldest_ancestor}");
-
-                        JobFound(job, oldest_ancestor)
-                    }
-                    None => JobNotFound,
-                };
-            }
+                search(config, true).await?
+            };
 
             //     5.3. If the "git ls-tree" are equals, write true in ci-skip file and exit with code 0
             // 6. If no job found, write false in ci-skip file and exit with code > 0
-            match process_result {
-                JobFound(..) => write_skip_done(&config.ci_skip_path, true).await?,
-                JobNotFound => write_skip_done(&config.ci_skip_path, false).await?,
-                SkipCiFileExists(..) => {}
-                Skip => {}
+            let skip_done = match process_result {
+                JobFound(..) | HistoryFound(..) => Some(true),
+                JobNotFound => Some(false),
+                SkipCiValue(skip_ci_value) => Some(skip_ci_value),
+                SkipCiFileExists(..) | Skip => None,
+            };
+            match skip_done {
+                Some(_) if config.dry_run => {
+                    verbose!("SKIP_CI_DRY_RUN=true → the ci-skip file is not written");
+                }
+                Some(skip_done) => write_skip_done(&config.ci_skip_path, skip_done).await?,
+                None => {}
             };
 
             process_result
@@ -73,6 +155,7 @@ async fn process(config: &Config) -> anyhow::Result<ProcessResult> {
 pub async fn process_with_exit_code(config_result: anyhow::Result<Config>) -> i32 {
     let start = Instant::now();
 
+    let dry_run = config_result.as_ref().is_ok_and(|config| config.dry_run);
     let exit_code = match config_result {
         Ok(config) => {
             let result = process(&config).await;
@@ -83,6 +166,10 @@ pub async fn process_with_exit_code(config_result: anyhow::Result<Config>) -> i3
                     green(&format!("✅ the oldest ancestor found : {oldest_ancestor}"));
                     0
                 }
+                Ok(HistoryFound(record)) => {
+                    green(&format!("✅ tree found in job {}  ", record.job_id));
+                    0
+                }
                 Ok(JobNotFound) => {
                     yellow("❌ tree not found in last jobs of the project");
                     1
@@ -97,6 +184,8 @@ pub async fn process_with_exit_code(config_result: anyhow::Result<Config>) -> i3
                     yellow("Skip the SkipCi process");
                     3
                 }
+                Ok(SkipCiValue(true)) => 0,
+                Ok(SkipCiValue(false)) => 3,
             }
         }
         Err(e) => {
@@ -105,6 +194,15 @@ pub async fn process_with_exit_code(config_result: anyhow::Result<Config>) -> i3
         }
     };
 
+    // the dry run never skips the job : the found tree has its own exit code
+    let exit_code = match exit_code {
+        0 if dry_run => {
+            yellow("🔍 SKIP_CI_DRY_RUN=true : the job would have been skipped");
+            4
+        }
+        exit_code => exit_code,
+    };
+
     let duration_micro = start.elapsed().as_nanos() / 1_000;
     verbose!(
         "exit code = {exit_code} ; duration : {}.{} ms",
@@ -116,8 +214,8 @@ pub async fn process_with_exit_code(config_result: anyhow::Result<Config>) -> i3
 
 #[cfg(test)]
 pub mod tests {
-    use crate::config::Config;
-    use crate::process::ProcessResult::{JobFound, JobNotFound, SkipCiFileExists};
+    use crate::config::{Backend, Config};
+    use crate::process::ProcessResult::{HistoryFound, JobFound, JobNotFound, SkipCiFileExists};
     use crate::process::{process, process_with_exit_code};
     use anyhow::Error;
     use git2::{Oid, Repository};
@@ -131,7 +229,7 @@ pub mod tests {
     use std::string::String;
     use tempfile::{tempdir, TempDir};
 
-    pub fn create_config_ok(tmp_dir: &TempDir, url: &String) -> Config {
+    pub fn create_config_ok(tmp_dir: &TempDir, url: &str) -> Config {
         Config {
             api_read_token: "aaa".to_string(),
             ci_commit_ref_name: Ok("branch1".to_string()),
@@ -140,12 +238,13 @@ pub mod tests {
             verbose: false,
             files_to_check: "root-1 Service-A/file-A1".to_string(),
             project_path: tmp_dir.path().to_str().unwrap().to_string(),
-            jobs_api_url: url.clone(),
+            jobs_api_url: url.to_string(),
             ci_skip_path: tmp_dir.path().join("ci-skip").to_str().unwrap().to_string(),
             page_to_fetch_max: 2,
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 2,
             skip: false,
+            ..Default::default()
         }
     }
 
@@ -164,6 +263,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 10,
             commit_to_check_same_job_max: 0,
             skip: false,
+            ..Default::default()
         }
     }
 
@@ -236,7 +336,7 @@ pub mod tests {
         server.expect(
             Expectation::matching(all_of!(
                 request::method_path("GET", "/api/123/jobs/12345678/trace",),
-                request::query(url_decoded(contains(("private_token", "aaa"))))
+                request::headers(contains(("private-token", "aaa")))
             ))
             .respond_with(status_code(200).body(raw)),
         );
@@ -264,7 +364,7 @@ pub mod tests {
         server.expect(
             Expectation::matching(all_of!(
                 request::method_path("GET", "/api/123/jobs/12345678/trace",),
-                request::query(url_decoded(contains(("private_token", "aaa"))))
+                request::headers(contains(("private-token", "aaa")))
             ))
             .respond_with(status_code(200).body("")),
         );
@@ -290,7 +390,7 @@ pub mod tests {
         server.expect(
             Expectation::matching(all_of!(
                 request::method_path("GET", "/api/123/jobs/12345678/trace",),
-                request::query(url_decoded(contains(("private_token", "aaa"))))
+                request::headers(contains(("private-token", "aaa")))
             ))
             .respond_with(status_code(200).body("")),
         );
@@ -311,6 +411,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 2,
             skip: false,
+            ..Default::default()
         };
         match process(&config).await.unwrap() {
             JobFound(job, oldest_ancestor) => {
@@ -332,7 +433,7 @@ pub mod tests {
         server.expect(
             Expectation::matching(all_of!(
                 request::method_path("GET", "/api/123/jobs/12345678/trace",),
-                request::query(url_decoded(contains(("private_token", "aaa"))))
+                request::headers(contains(("private-token", "aaa")))
             ))
             .respond_with(status_code(200).body(raw)),
         );
@@ -349,6 +450,45 @@ pub mod tests {
         assert_eq!(res, 0);
     }
 
+    #[tokio::test]
+    async fn test_process_with_exit_code_dry_run() {
+        let (tmp_dir, repo) = prepare_tmp_repo();
+        let server = Server::run();
+        // the trace isn't parsed : no oldest ancestor marker in the log of the dry run
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/123/jobs/12345678/trace"))
+                .times(0)
+                .respond_with(status_code(200).body("")),
+        );
+        let url = add_jobs_expect(&server);
+        // commit04
+        repo.set_head_detached(Oid::from_str("5e694dadd2979a2680c98c88a2f98df9787947d2").unwrap())
+            .unwrap();
+        let config = Config {
+            dry_run: true,
+            ..create_config_ok(&tmp_dir, &url)
+        };
+        match process(&config).await.unwrap() {
+            JobFound(job, oldest_ancestor) => assert_eq!(oldest_ancestor, job.web_url),
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(!Path::new(&config.ci_skip_path).exists());
+        assert_eq!(process_with_exit_code(Ok(config.clone())).await, 4);
+        assert!(!Path::new(&config.ci_skip_path).exists());
+
+        // not found : no ci-skip file and no record of the cache backend
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        fs::write(&history_path, "abc:1\n").unwrap();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            ..config
+        };
+        assert_eq!(process_with_exit_code(Ok(config.clone())).await, 1);
+        assert!(!Path::new(&config.ci_skip_path).exists());
+        assert_eq!(fs::read_to_string(&history_path).unwrap(), "abc:1\n");
+    }
+
     #[tokio::test]
     async fn test_process_with_exit_code_job_not_found() {
         let (tmp_dir, _) = prepare_tmp_repo();
@@ -368,6 +508,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 10,
             commit_to_check_same_job_max: 0,
             skip: false,
+            ..Default::default()
         };
         let res = process_with_exit_code(Ok(config.clone())).await;
         assert_eq!(res, 1);
@@ -390,6 +531,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 10,
             commit_to_check_same_job_max: 0,
             skip: true,
+            ..Default::default()
         };
         let res = process_with_exit_code(Ok(config.clone())).await;
         assert_eq!(res, 3);
@@ -414,6 +556,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 2,
             commit_to_check_same_job_max: 2,
             skip: false,
+            ..Default::default()
         };
         let res = process(&config).await.unwrap();
         assert!(matches!(res, JobNotFound));
@@ -438,6 +581,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 0,
             commit_to_check_same_job_max: 1,
             skip: false,
+            ..Default::default()
         };
         let res = process(&config).await.unwrap();
         assert!(matches!(res, JobNotFound));
@@ -462,6 +606,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 10,
             commit_to_check_same_job_max: 0,
             skip: false,
+            ..Default::default()
         };
         let res = process(&config).await.unwrap();
         assert!(matches!(res, JobNotFound));
@@ -493,6 +638,7 @@ pub mod tests {
                 commit_to_check_same_ref_max: 10,
                 commit_to_check_same_job_max: 0,
                 skip: false,
+                ..Default::default()
             };
             let _res = process_with_exit_code(Ok(config));
         });
@@ -524,6 +670,7 @@ pub mod tests {
             commit_to_check_same_ref_max: 0,
             commit_to_check_same_job_max: 0,
             skip: false,
+            ..Default::default()
         };
         let path = tmp_dir.path().join("skip-ci");
         fs::write(&path, "true").unwrap();
@@ -545,4 +692,69 @@ pub mod tests {
         let res = process_with_exit_code(Ok(config)).await;
         assert_eq!(res, 3);
     }
+
+    #[tokio::test]
+    async fn test_process_cache_found() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        fs::write(
+            &history_path,
+            "0000000000000000000000000000000000000000:1\n\
+             3de26cd90c29f5a3e937bca57e603b1f58f00026:12345678\n",
+        )
+        .unwrap();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            no_artifact: true,
+            ci_job_id: 456,
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        let res = process(&config).await.unwrap();
+        match res {
+            HistoryFound(record) => assert_eq!(record.job_id, 12345678),
+            _ => panic!(),
+        }
+        let skip_ci = fs::read_to_string(tmp_dir.path().join("ci-skip")).unwrap();
+        assert_eq!(skip_ci, "true");
+        assert_eq!(process_with_exit_code(Ok(config)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_process_cache_not_found() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            ci_job_id: 456,
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        let res = process(&config).await.unwrap();
+        assert!(matches!(res, JobNotFound));
+        let history = fs::read_to_string(history_path).unwrap();
+        assert_eq!(history, "3de26cd90c29f5a3e937bca57e603b1f58f00026:456\n");
+        assert_eq!(process_with_exit_code(Ok(config)).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_process_skip_ci_value() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            skip_ci_value: Some(true),
+            ..create_config_no_url(&tmp_dir)
+        };
+        assert_eq!(process_with_exit_code(Ok(config)).await, 0);
+        let skip_ci = fs::read_to_string(tmp_dir.path().join("skip-ci")).unwrap();
+        assert_eq!(skip_ci, "true");
+
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            skip_ci_value: Some(false),
+            ..create_config_no_url(&tmp_dir)
+        };
+        assert_eq!(process_with_exit_code(Ok(config)).await, 3);
+        let skip_ci = fs::read_to_string(tmp_dir.path().join("skip-ci")).unwrap();
+        assert_eq!(skip_ci, "false");
+    }
 }
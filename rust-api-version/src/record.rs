@@ -0,0 +1,134 @@
+use crate::config::Config;
+use crate::find_last_job_ok::get_head_fingerprint;
+use crate::history::{record_in_history, HistoryRecord};
+use crate::record::RecordResult::{JobNotSuccessful, NoHistoryStore, Recorded};
+use crate::skipci_log::{green, red, yellow};
+use crate::verbose;
+
+#[derive(Debug, PartialEq)]
+pub enum RecordResult {
+    Recorded(HistoryRecord),
+    JobNotSuccessful(String),
+    NoHistoryStore,
+}
+
+// to run in the after_script : if CI_JOB_STATUS=success, add the fingerprint of the HEAD and
+// the current job to the history store of the backend
+async fn record(config: &Config) -> anyhow::Result<RecordResult> {
+    let ci_job_status = config.ci_job_status.clone().unwrap_or_default();
+    if ci_job_status != "success" {
+        return Ok(JobNotSuccessful(ci_job_status));
+    }
+    let fingerprint = get_head_fingerprint(config)?;
+    let record = HistoryRecord::of_current_job(config, &fingerprint);
+    if record_in_history(config, &record).await? {
+        Ok(Recorded(record))
+    } else {
+        Ok(NoHistoryStore)
+    }
+}
+
+pub async fn record_with_exit_code(config_result: anyhow::Result<Config>) -> i32 {
+    match config_result {
+        Ok(config) => {
+            let result = record(&config).await;
+            verbose!("record result = {result:?}");
+            match result {
+                Ok(Recorded(record)) => {
+                    green(&format!("✅ recorded : {record}"));
+                    0
+                }
+                Ok(JobNotSuccessful(status)) => {
+                    yellow(&format!("CI_JOB_STATUS={status} → nothing to record"));
+                    3
+                }
+                Ok(NoHistoryStore) => {
+                    yellow("the backend has no history store → nothing to record");
+                    3
+                }
+                Err(e) => {
+                    red(&format!("❌ RECORD ERROR : \n{e:#?}"));
+                    2
+                }
+            }
+        }
+        Err(e) => {
+            red(&format!("❌ CONFIG ERROR : \n{e:#?}"));
+            6
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::process::tests::{create_config_ok, prepare_tmp_repo};
+    use crate::record::RecordResult::{JobNotSuccessful, NoHistoryStore, Recorded};
+    use crate::record::{record, record_with_exit_code};
+    use anyhow::Error;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_record_cache() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let history_path = tmp_dir.path().join("ci_ok_history");
+        fs::write(&history_path, "abc:1\n").unwrap();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_ok_history_path: history_path.to_str().unwrap().to_string(),
+            ci_job_id: 456,
+            ci_job_status: Ok("success".to_string()),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        match record(&config).await.unwrap() {
+            Recorded(record) => {
+                assert_eq!(
+                    record.fingerprint,
+                    "3de26cd90c29f5a3e937bca57e603b1f58f00026"
+                );
+                assert_eq!(record.job_id, 456);
+                assert_eq!(record.job_ref, Some("branch1".to_string()));
+            }
+            _ => panic!(),
+        }
+        let history = fs::read_to_string(&history_path).unwrap();
+        assert!(history.starts_with("abc:1\n3de26cd90c29f5a3e937bca57e603b1f58f00026:456:branch1:"));
+        assert_eq!(record_with_exit_code(Ok(config)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_record_job_not_successful() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_job_status: Ok("failed".to_string()),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        let res = record(&config).await.unwrap();
+        assert_eq!(res, JobNotSuccessful("failed".to_string()));
+        assert_eq!(record_with_exit_code(Ok(config)).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_record_api_backend() {
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            ci_job_status: Ok("success".to_string()),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        assert_eq!(record(&config).await.unwrap(), NoHistoryStore);
+    }
+
+    #[tokio::test]
+    async fn test_record_with_exit_code_errors() {
+        assert_eq!(record_with_exit_code(Err(Error::msg("error"))).await, 6);
+        let (tmp_dir, _) = prepare_tmp_repo();
+        let config = Config {
+            backend: Backend::Cache,
+            ci_job_status: Ok("success".to_string()),
+            files_to_check: "".to_string(),
+            ..create_config_ok(&tmp_dir, "____")
+        };
+        assert_eq!(record_with_exit_code(Ok(config)).await, 2);
+    }
+}
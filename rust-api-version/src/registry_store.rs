@@ -0,0 +1,250 @@
+use crate::config::Config;
+use crate::find_last_job_ok::get_job_names;
+use crate::history::HistoryRecord;
+use crate::http_client::{read_body, send};
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+// the package version of the job : the characters other than [A-Za-z0-9_-] are encoded as +XX,
+// the package versions of the generic packages must match \A(\.?[\w\+-]+\.?)+\z
+fn encode_package_version(job_name: &str) -> String {
+    job_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => (b as char).to_string(),
+            _ => format!("+{b:02X}"),
+        })
+        .collect()
+}
+
+// <CI_API_V4_URL>/projects/<CI_PROJECT_ID>/packages/generic/skip-ci/<job name>/<fingerprint>.json
+fn get_record_url(config: &Config, job_name: &str, fingerprint: &str) -> String {
+    format!(
+        "{}/{}/{fingerprint}.json",
+        config.registry_api_url,
+        encode_package_version(job_name)
+    )
+}
+
+async fn registry_request(
+    config: &Config,
+    method: Method,
+    url: &str,
+    body: Vec<u8>,
+) -> anyhow::Result<Response<Body>> {
+    verbose!("registry {method} {url}");
+    let credential = config.job_credential()?;
+    send(&config.http_settings(), || {
+        credential
+            .authorize(Request::builder().method(method.clone()).uri(url))
+            .body(Body::from(body.clone()))
+            .context("registry request error")
+    })
+    .await
+    .context("registry request error")
+}
+
+// the record of the job, then the records of its aliases
+pub async fn find_in_registry(
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    for job_name in get_job_names(config) {
+        let record = find_record(config, &get_record_url(config, job_name, fingerprint)).await?;
+        if record.is_some() {
+            return Ok(record);
+        }
+    }
+    Ok(None)
+}
+
+async fn find_record(config: &Config, url: &str) -> anyhow::Result<Option<HistoryRecord>> {
+    let response = registry_request(config, Method::GET, url, vec![]).await?;
+    let status = response.status();
+    verbose!("registry status {status:?}");
+    let body = read_body(&config.http_settings(), response)
+        .await
+        .context("registry response error")?;
+    match status {
+        StatusCode::NOT_FOUND => {
+            verbose!("{url} not found in the registry");
+            Ok(None)
+        }
+        status if status.is_success() => {
+            let record = serde_json::from_slice::<HistoryRecord>(&body)
+                .context("parse registry record error")?;
+            verbose!("record found in the registry : {record}");
+            Ok(Some(record))
+        }
+        status => Err(anyhow!(
+            "registry GET error {status} : {}",
+            String::from_utf8_lossy(&body)
+        )),
+    }
+}
+
+pub async fn add_to_registry(config: &Config, record: &HistoryRecord) -> anyhow::Result<()> {
+    let url = get_record_url(config, &config.ci_job_name, &record.fingerprint);
+    let body = serde_json::to_vec(record).context("serialize record error")?;
+    let response = registry_request(config, Method::PUT, &url, body).await?;
+    let status = response.status();
+    verbose!("registry status {status:?}");
+    if status.is_success() {
+        verbose!("{record} written in the registry");
+        Ok(())
+    } else {
+        let body = read_body(&config.http_settings(), response)
+            .await
+            .unwrap_or_default();
+        Err(anyhow!(
+            "registry PUT error {status} : {}",
+            String::from_utf8_lossy(&body)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::history::tests::record;
+    use crate::process::tests::create_config_ok;
+    use crate::registry_store::{add_to_registry, encode_package_version, find_in_registry};
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use std::env::VarError;
+    use tempfile::{tempdir, TempDir};
+
+    const RECORD_JSON: &str =
+        r#"{"fingerprint":"abc","job_id":456,"job_ref":"main","timestamp":1700000000}"#;
+    const RECORD_PATH: &str = "/api/projects/123/packages/generic/skip-ci/test+201+2F3/abc.json";
+
+    fn create_config(tmp_dir: &TempDir, server: &Server) -> Config {
+        Config {
+            backend: Backend::Registry,
+            ci_job_name: "test 1/3".to_string(),
+            ci_job_token: Ok("__CI_JOB_TOKEN__".to_string()),
+            registry_api_url: server.url_str("/api/projects/123/packages/generic/skip-ci"),
+            ..create_config_ok(tmp_dir, "____")
+        }
+    }
+
+    #[test]
+    fn test_encode_package_version() {
+        assert_eq!(encode_package_version("jobA"), "jobA");
+        assert_eq!(encode_package_version("test 1/3"), "test+201+2F3");
+        assert_eq!(encode_package_version("a.b+c"), "a+2Eb+2Bc");
+    }
+
+    #[tokio::test]
+    async fn test_find_in_registry() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", RECORD_PATH),
+                request::headers(contains(("job-token", "__CI_JOB_TOKEN__"))),
+            ])
+            .respond_with(status_code(200).body(RECORD_JSON)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config(&tmp_dir, &server);
+        assert_eq!(
+            find_in_registry(&config, "abc").await.unwrap(),
+            Some(record("abc", 456))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_in_registry_alias() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/api/projects/123/packages/generic/skip-ci/test-unit/abc.json",
+            ))
+            .respond_with(status_code(404)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", RECORD_PATH))
+                .respond_with(status_code(200).body(RECORD_JSON)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = Config {
+            ci_job_name: "test-unit".to_string(),
+            job_name_aliases: vec!["test 1/3".to_string()],
+            ..create_config(&tmp_dir, &server)
+        };
+        assert_eq!(
+            find_in_registry(&config, "abc").await.unwrap(),
+            Some(record("abc", 456))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_in_registry_not_found() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", RECORD_PATH))
+                .respond_with(status_code(404).body(r#"{"message":"404 Package Not Found"}"#)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config(&tmp_dir, &server);
+        assert_eq!(find_in_registry(&config, "abc").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_find_in_registry_ko() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", RECORD_PATH))
+                .respond_with(status_code(401).body(r#"{"message":"401 Unauthorized"}"#)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config(&tmp_dir, &server);
+        let err = find_in_registry(&config, "abc").await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            r#"registry GET error 401 Unauthorized : {"message":"401 Unauthorized"}"#
+        );
+        let config = Config {
+            ci_job_token: Err(VarError::NotPresent),
+            ..config
+        };
+        let err = find_in_registry(&config, "abc").await.err().unwrap();
+        assert_eq!(err.to_string(), "CI_JOB_TOKEN undefined");
+    }
+
+    #[tokio::test]
+    async fn test_add_to_registry() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", RECORD_PATH),
+                request::headers(contains(("job-token", "__CI_JOB_TOKEN__"))),
+                request::body(RECORD_JSON),
+            ])
+            .respond_with(status_code(201).body(r#"{"message":"201 Created"}"#)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config(&tmp_dir, &server);
+        add_to_registry(&config, &record("abc", 456)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_to_registry_ko() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("PUT", RECORD_PATH))
+                .respond_with(status_code(403).body(r#"{"message":"403 Forbidden"}"#)),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let config = create_config(&tmp_dir, &server);
+        let err = add_to_registry(&config, &record("abc", 456))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            r#"registry PUT error 403 Forbidden : {"message":"403 Forbidden"}"#
+        );
+    }
+}
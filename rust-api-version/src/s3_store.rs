@@ -0,0 +1,476 @@
+use crate::artifact::extract_archive;
+use crate::config::Config;
+use crate::find_last_job_ok::get_job_names;
+use crate::fs_store::encode_job_name;
+use crate::history::HistoryRecord;
+use crate::http_client::{read_body, send};
+use crate::sigv4::{format_amz_date, get_authorization, sha256_hex, uri_encode, Credentials};
+use crate::skipci_log::yellow;
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::tempdir;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// <SKIP_CI_S3_PREFIX>/<CI_PROJECT_ID>/<job name>/<fingerprint>.<extension>
+fn get_object_key(config: &Config, job_name: &str, fingerprint: &str, extension: &str) -> String {
+    let prefix = config.s3_prefix.trim_matches('/');
+    let key = format!(
+        "{}/{}/{fingerprint}.{extension}",
+        config.ci_project_id,
+        encode_job_name(job_name)
+    );
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{prefix}/{key}")
+    }
+}
+
+// path-style url : <SKIP_CI_S3_ENDPOINT>/<SKIP_CI_S3_BUCKET>/<key>
+fn get_object_uri(config: &Config, key: &str) -> anyhow::Result<Uri> {
+    format!(
+        "{}/{}/{}",
+        config.s3_endpoint.trim_end_matches('/'),
+        uri_encode(&config.s3_bucket, true),
+        uri_encode(key, false)
+    )
+    .parse()
+    .context("parse s3 url error")
+}
+
+async fn s3_request(
+    config: &Config,
+    method: Method,
+    key: &str,
+    body: Vec<u8>,
+) -> anyhow::Result<Response<Body>> {
+    let uri = get_object_uri(config, key)?;
+    verbose!("s3 {method} {uri}");
+    let host = uri.authority().context("s3 url without host")?.to_string();
+    let payload_sha256 = sha256_hex(&body);
+    let credentials = Credentials {
+        access_key_id: &config.s3_access_key_id,
+        secret_access_key: &config.s3_secret_access_key,
+        region: &config.s3_region,
+    };
+    // signed again on each retry : the signature expires
+    send(&config.http_settings(), || {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let amz_date = format_amz_date(timestamp);
+        let authorization = get_authorization(
+            &credentials,
+            method.as_str(),
+            uri.path(),
+            "",
+            &[
+                ("host", &host),
+                ("x-amz-content-sha256", &payload_sha256),
+                ("x-amz-date", &amz_date),
+            ],
+            &payload_sha256,
+            &amz_date,
+        );
+        Request::builder()
+            .method(method.clone())
+            .uri(uri.clone())
+            .header("host", &host)
+            .header("x-amz-content-sha256", &payload_sha256)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .body(Body::from(body.clone()))
+            .context("s3 request error")
+    })
+    .await
+    .context("s3 request error")
+}
+
+// GET the object, None if it doesn't exist
+async fn get_object(config: &Config, key: &str) -> anyhow::Result<Option<hyper::body::Bytes>> {
+    let response = s3_request(config, Method::GET, key, vec![]).await?;
+    let status = response.status();
+    verbose!("s3 status {status:?}");
+    let body = read_body(&config.http_settings(), response)
+        .await
+        .context("s3 response error")?;
+    match status {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => Ok(Some(body)),
+        status => Err(anyhow!(
+            "s3 GET {key} error {status} : {}",
+            String::from_utf8_lossy(&body)
+        )),
+    }
+}
+
+async fn put_object(config: &Config, key: &str, body: Vec<u8>) -> anyhow::Result<()> {
+    let response = s3_request(config, Method::PUT, key, body).await?;
+    let status = response.status();
+    verbose!("s3 status {status:?}");
+    if status.is_success() {
+        Ok(())
+    } else {
+        let body = read_body(&config.http_settings(), response)
+            .await
+            .unwrap_or_default();
+        Err(anyhow!(
+            "s3 PUT {key} error {status} : {}",
+            String::from_utf8_lossy(&body)
+        ))
+    }
+}
+
+// the record of the job, then the records of its aliases
+pub async fn find_in_s3_store(
+    config: &Config,
+    fingerprint: &str,
+) -> anyhow::Result<Option<HistoryRecord>> {
+    for job_name in get_job_names(config) {
+        let key = get_object_key(config, job_name, fingerprint, "json");
+        match get_object(config, &key).await? {
+            Some(body) => {
+                let record = serde_json::from_slice::<HistoryRecord>(&body)
+                    .with_context(|| format!("parse {key} error"))?;
+                verbose!("record found in {key} : {record}");
+                return Ok(Some(record));
+            }
+            None => verbose!("{key} not found"),
+        }
+    }
+    Ok(None)
+}
+
+// PUT the record and the zip of SKIP_CI_S3_ARTIFACT_PATHS
+pub async fn add_to_s3_store(config: &Config, record: &HistoryRecord) -> anyhow::Result<()> {
+    if !config.s3_artifact_paths.is_empty() {
+        let tmp_dir = tempdir().context("Create temp dir error")?;
+        let zip_path = tmp_dir.path().join("artifact.zip");
+        let paths = config.s3_artifact_paths.split_whitespace().collect();
+        zip_paths(&config.project_path, &paths, &zip_path)?;
+        let zip = fs::read(&zip_path).context("read artifact zip error")?;
+        put_object(
+            config,
+            &get_object_key(config, &config.ci_job_name, &record.fingerprint, "zip"),
+            zip,
+        )
+        .await?;
+    }
+    let key = get_object_key(config, &config.ci_job_name, &record.fingerprint, "json");
+    let body = serde_json::to_vec(record).context("serialize record error")?;
+    put_object(config, &key, body).await?;
+    verbose!("{record} written in {key}");
+    Ok(())
+}
+
+// download and extract the zip of the record (of the job or of one of its aliases), return false
+// if the zip is not found
+pub async fn extract_s3_artifact(config: &Config, record: &HistoryRecord) -> anyhow::Result<bool> {
+    for job_name in get_job_names(config) {
+        let key = get_object_key(config, job_name, &record.fingerprint, "zip");
+        if let Some(zip) = get_object(config, &key).await? {
+            let tmp_dir = tempdir().context("Create temp dir error")?;
+            let zip_path = tmp_dir.path().join("artifact.zip");
+            let zip_path = zip_path.to_str().context("Error path to str")?;
+            fs::write(zip_path, zip).context("write artifact zip error")?;
+            extract_archive(zip_path, &config.project_path)?;
+            verbose!("extract_s3_artifact is OK");
+            return Ok(true);
+        }
+    }
+    yellow("artifact not found");
+    Ok(false)
+}
+
+// zip the paths (files or directories) relative to the project path
+fn zip_paths(project_path: &str, paths: &Vec<&str>, zip_path: &Path) -> anyhow::Result<()> {
+    verbose!("zip {paths:?} to {zip_path:?}");
+    let file = File::create(zip_path).context("create artifact zip error")?;
+    let mut zip = ZipWriter::new(file);
+    for path in paths {
+        add_to_zip(&mut zip, Path::new(project_path), Path::new(path))?;
+    }
+    zip.finish().context("write artifact zip error")?;
+    Ok(())
+}
+
+fn add_to_zip(zip: &mut ZipWriter<File>, root: &Path, path: &Path) -> anyhow::Result<()> {
+    let full_path = root.join(path);
+    // the links aren't followed : a link to a parent directory would loop forever
+    let metadata = fs::symlink_metadata(&full_path)
+        .with_context(|| format!("artifact path {path:?} error"))?;
+    if metadata.is_symlink() {
+        verbose!("the symbolic link {path:?} is not added to the artifact zip");
+        return Ok(());
+    }
+    let name = path.to_str().context("Error path to str")?;
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(metadata.permissions().mode());
+    if metadata.is_dir() {
+        zip.add_directory(name, options)
+            .context("write artifact zip error")?;
+        let mut entries = fs::read_dir(&full_path)
+            .with_context(|| format!("artifact path {path:?} error"))?
+            .flatten()
+            .map(|entry| path.join(entry.file_name()))
+            .collect::<Vec<_>>();
+        entries.sort();
+        for entry in entries {
+            add_to_zip(zip, root, &entry)?;
+        }
+    } else {
+        zip.start_file(name, options)
+            .context("write artifact zip error")?;
+        zip.write_all(&fs::read(&full_path).with_context(|| format!("read {path:?} error"))?)
+            .context("write artifact zip error")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{Backend, Config};
+    use crate::history::tests::record;
+    use crate::process::tests::create_config_ok;
+    use crate::s3_store::{
+        add_to_s3_store, extract_s3_artifact, find_in_s3_store, get_object_key, zip_paths,
+    };
+    use httptest::bytes::Bytes;
+    use httptest::http::Request;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+    use std::fs;
+    use std::fs::File;
+    use tempfile::{tempdir, TempDir};
+
+    const RECORD_JSON: &str =
+        r#"{"fingerprint":"abc","job_id":456,"job_ref":"main","timestamp":1700000000}"#;
+    const RECORD_PATH: &str = "/bucket/skip-ci/123/test%25201%252F3/abc.json";
+    const ZIP_PATH: &str = "/bucket/skip-ci/123/test%25201%252F3/abc.zip";
+
+    fn create_config(tmp_dir: &TempDir, server: &Server) -> Config {
+        Config {
+            backend: Backend::S3,
+            ci_project_id: "123".to_string(),
+            ci_job_name: "test 1/3".to_string(),
+            s3_endpoint: server.url_str(""),
+            s3_bucket: "bucket".to_string(),
+            s3_prefix: "/skip-ci/".to_string(),
+            s3_region: "us-east-1".to_string(),
+            s3_access_key_id: "AK".to_string(),
+            s3_secret_access_key: "SK".to_string(),
+            ..create_config_ok(tmp_dir, "____")
+        }
+    }
+
+    fn signed_request(method: &'static str, path: &'static str) -> impl Matcher<Request<Bytes>> {
+        all_of![
+            request::method_path(method, path),
+            request::headers(contains(key("x-amz-date"))),
+            request::headers(contains(key("x-amz-content-sha256"))),
+            request::headers(contains((
+                "authorization",
+                matches("^AWS4-HMAC-SHA256 Credential=AK/[0-9]{8}/us-east-1/s3/aws4_request,SignedHeaders=host;x-amz-content-sha256;x-amz-date,Signature=[0-9a-f]{64}$")
+            ))),
+        ]
+    }
+
+    #[test]
+    fn test_get_object_key() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        let config = create_config(&tmp_dir, &server);
+        assert_eq!(
+            get_object_key(&config, "test 1/3", "abc", "json"),
+            "skip-ci/123/test%201%2F3/abc.json"
+        );
+        let config = Config {
+            s3_prefix: "".to_string(),
+            ..config
+        };
+        assert_eq!(
+            get_object_key(&config, "test 1/3", "abc", "zip"),
+            "123/test%201%2F3/abc.zip"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_in_s3_store() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(signed_request("GET", RECORD_PATH))
+                .respond_with(status_code(200).body(RECORD_JSON)),
+        );
+        let config = create_config(&tmp_dir, &server);
+        assert_eq!(
+            find_in_s3_store(&config, "abc").await.unwrap(),
+            Some(record("abc", 456))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_in_s3_store_alias() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/bucket/skip-ci/123/test-unit/abc.json",
+            ))
+            .respond_with(status_code(404)),
+        );
+        server.expect(
+            Expectation::matching(signed_request("GET", RECORD_PATH))
+                .respond_with(status_code(200).body(RECORD_JSON)),
+        );
+        let config = Config {
+            ci_job_name: "test-unit".to_string(),
+            job_name_aliases: vec!["test 1/3".to_string()],
+            ..create_config(&tmp_dir, &server)
+        };
+        assert_eq!(
+            find_in_s3_store(&config, "abc").await.unwrap(),
+            Some(record("abc", 456))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_in_s3_store_not_found() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", RECORD_PATH))
+                .respond_with(status_code(404).body("<Error><Code>NoSuchKey</Code></Error>")),
+        );
+        let config = create_config(&tmp_dir, &server);
+        assert_eq!(find_in_s3_store(&config, "abc").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_find_in_s3_store_ko() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", RECORD_PATH)).respond_with(
+                status_code(403).body("<Error><Code>SignatureDoesNotMatch</Code></Error>"),
+            ),
+        );
+        let config = create_config(&tmp_dir, &server);
+        let err = find_in_s3_store(&config, "abc").await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "s3 GET skip-ci/123/test%201%2F3/abc.json error 403 Forbidden : <Error><Code>SignatureDoesNotMatch</Code></Error>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_to_s3_store() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                signed_request("PUT", RECORD_PATH),
+                request::body(RECORD_JSON)
+            ])
+            .respond_with(status_code(200)),
+        );
+        let config = create_config(&tmp_dir, &server);
+        add_to_s3_store(&config, &record("abc", 456)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_s3_artifact() {
+        let tmp_dir = tempdir().unwrap();
+        fs::create_dir_all(tmp_dir.path().join("target/doc")).unwrap();
+        fs::write(tmp_dir.path().join("target/doc/index.html"), "<html/>").unwrap();
+        fs::write(tmp_dir.path().join("report.xml"), "<xml/>").unwrap();
+        // a link loop isn't followed
+        std::os::unix::fs::symlink("..", tmp_dir.path().join("target/doc/up")).unwrap();
+        let zip_path = tmp_dir.path().join("artifact.zip");
+        zip_paths(
+            tmp_dir.path().to_str().unwrap(),
+            &vec!["target", "report.xml"],
+            &zip_path,
+        )
+        .unwrap();
+        let zip = fs::read(&zip_path).unwrap();
+        let archive = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let mut names = archive.file_names().collect::<Vec<&str>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "report.xml",
+                "target/",
+                "target/doc/",
+                "target/doc/index.html"
+            ]
+        );
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(signed_request("PUT", ZIP_PATH)).respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(signed_request("PUT", RECORD_PATH))
+                .respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(signed_request("GET", ZIP_PATH))
+                .respond_with(status_code(200).body(zip)),
+        );
+        let config = Config {
+            s3_artifact_paths: "target report.xml".to_string(),
+            ..create_config(&tmp_dir, &server)
+        };
+        add_to_s3_store(&config, &record("abc", 456)).await.unwrap();
+
+        let out_dir = tempdir().unwrap();
+        let config = Config {
+            project_path: out_dir.path().to_str().unwrap().to_string(),
+            ..config
+        };
+        assert!(extract_s3_artifact(&config, &record("abc", 456))
+            .await
+            .unwrap());
+        assert_eq!(
+            fs::read_to_string(out_dir.path().join("target/doc/index.html")).unwrap(),
+            "<html/>"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_s3_artifact_ko() {
+        let tmp_dir = tempdir().unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", ZIP_PATH))
+                .respond_with(status_code(404)),
+        );
+        let config = Config {
+            s3_artifact_paths: "not-found".to_string(),
+            ..create_config(&tmp_dir, &server)
+        };
+        assert!(!extract_s3_artifact(&config, &record("abc", 456))
+            .await
+            .unwrap());
+        let err = add_to_s3_store(&config, &record("abc", 456))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            format!("{err:#}"),
+            "artifact path \"not-found\" error: No such file or directory (os error 2)"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use crate::verbose;
+use anyhow::{anyhow, Context};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore};
+use rustls_pemfile::Item;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// the extra roots and the client certificate of the TLS connections
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TlsSettings {
+    // SKIP_CI_CA_FILE : added to the webpki roots, an error if it can't be read
+    pub ca_file: String,
+    // CI_SERVER_TLS_CA_FILE and SSL_CERT_FILE : added to the webpki roots, skipped if missing
+    pub optional_ca_files: Vec<String>,
+    // SKIP_CI_TLS_CERT_FILE (or CI_SERVER_TLS_CERT_FILE) : the client certificate of the mTLS
+    pub cert_file: String,
+    // SKIP_CI_TLS_KEY_FILE (or CI_SERVER_TLS_KEY_FILE) : the private key of the client certificate
+    pub key_file: String,
+}
+
+// the certificates and the keys of a PEM file
+pub fn read_pem_file(path: &str) -> anyhow::Result<Vec<Item>> {
+    let file = File::open(path).with_context(|| format!("read {path} error"))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .with_context(|| format!("{path} : invalid PEM file"))
+}
+
+fn read_certificates(path: &str) -> anyhow::Result<Vec<Certificate>> {
+    let certificates = read_pem_file(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect::<Vec<Certificate>>();
+    if certificates.is_empty() {
+        Err(anyhow!("no certificate in {path}"))
+    } else {
+        Ok(certificates)
+    }
+}
+
+fn read_private_key(path: &str) -> anyhow::Result<PrivateKey> {
+    read_pem_file(path)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .with_context(|| format!("no private key in {path}"))
+}
+
+// the webpki roots and the certificates of the CA files
+fn get_root_cert_store(settings: &TlsSettings) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    // the CA files of the runner and of the system may be missing in the image of the job
+    let optional_ca_files = settings.optional_ca_files.iter().filter(|ca_file| {
+        let exists = Path::new(ca_file).exists();
+        if !exists {
+            verbose!("warning : the CA file {ca_file} doesn't exist, skipped");
+        }
+        exists
+    });
+    let ca_files = [&settings.ca_file]
+        .into_iter()
+        .filter(|ca_file| !ca_file.is_empty())
+        .chain(optional_ca_files);
+    // the system bundles (SSL_CERT_FILE) hold certificates that webpki can't parse
+    for ca_file in ca_files {
+        let certificates = read_certificates(ca_file)?
+            .into_iter()
+            .map(|certificate| certificate.0)
+            .collect::<Vec<Vec<u8>>>();
+        match roots.add_parsable_certificates(&certificates) {
+            (0, _) => return Err(anyhow!("invalid certificates in {ca_file}")),
+            (added, ignored) => {
+                verbose!("{added} CA certificates of {ca_file} added, {ignored} ignored")
+            }
+        }
+    }
+    Ok(roots)
+}
+
+pub fn get_client_config(settings: &TlsSettings) -> anyhow::Result<ClientConfig> {
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(get_root_cert_store(settings)?);
+    if settings.cert_file.is_empty() {
+        Ok(builder.with_no_client_auth())
+    } else {
+        builder
+            .with_single_cert(
+                read_certificates(&settings.cert_file)?,
+                read_private_key(&settings.key_file)?,
+            )
+            .with_context(|| format!("invalid private key in {}", settings.key_file))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::http_client::tests::fast_http_settings;
+    use crate::http_client::{read_body, send, HttpSettings, SharedClient};
+    use crate::tls::{get_client_config, read_pem_file, TlsSettings};
+    use anyhow::Context;
+    use hyper::{Body, Request, StatusCode};
+    use rustls::server::AllowAnyAuthenticatedClient;
+    use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+    use rustls_pemfile::Item;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    pub const CA_FILE: &str = "test/tls/ca.pem";
+
+    fn read_der(path: &str) -> Vec<u8> {
+        match read_pem_file(path).unwrap().remove(0) {
+            Item::X509Certificate(der) | Item::PKCS8Key(der) | Item::RSAKey(der) => der,
+            item => panic!("unexpected {item:?} in {path}"),
+        }
+    }
+
+    // a https server of localhost signed by test/tls/ca.pem, "ok" to the first request
+    async fn run_tls_server(require_client_cert: bool) -> u16 {
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = if require_client_cert {
+            let mut roots = RootCertStore::empty();
+            roots.add(&Certificate(read_der(CA_FILE))).unwrap();
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        } else {
+            builder.with_no_client_auth()
+        };
+        let server_config = builder
+            .with_single_cert(
+                vec![Certificate(read_der("test/tls/server.pem"))],
+                PrivateKey(read_der("test/tls/server.key")),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let Ok(mut stream) = acceptor.accept(stream).await else {
+                return;
+            };
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+        });
+        port
+    }
+
+    async fn get(port: u16, settings: &HttpSettings) -> anyhow::Result<String> {
+        let url = format!("https://localhost:{port}/api/v4/projects");
+        let response = send(settings, || {
+            Request::get(&url)
+                .body(Body::empty())
+                .context("request error")
+        })
+        .await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = read_body(settings, response).await?;
+        Ok(String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn test_get_client_config_ko() {
+        let err =
+            |settings: TlsSettings| format!("{:#}", get_client_config(&settings).err().unwrap());
+        assert_eq!(
+            err(TlsSettings {
+                ca_file: "test/tls/not-found.pem".to_string(),
+                ..Default::default()
+            }),
+            "read test/tls/not-found.pem error: No such file or directory (os error 2)"
+        );
+        assert!(get_client_config(&TlsSettings {
+            optional_ca_files: vec!["test/tls/not-found.pem".to_string()],
+            ..Default::default()
+        })
+        .is_ok());
+        assert_eq!(
+            err(TlsSettings {
+                optional_ca_files: vec!["test/tls/server.key".to_string()],
+                ..Default::default()
+            }),
+            "no certificate in test/tls/server.key"
+        );
+        assert_eq!(
+            err(TlsSettings {
+                cert_file: "test/tls/client.pem".to_string(),
+                key_file: "test/tls/client.pem".to_string(),
+                ..Default::default()
+            }),
+            "no private key in test/tls/client.pem"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca() {
+        let settings = HttpSettings {
+            retry_max: 0,
+            ..fast_http_settings()
+        };
+        let port = run_tls_server(false).await;
+        let err = get(port, &settings).await.err().unwrap();
+        assert!(format!("{err:#}").contains("UnknownIssuer"), "{err:#}");
+
+        let settings = HttpSettings {
+            tls: TlsSettings {
+                ca_file: CA_FILE.to_string(),
+                ..Default::default()
+            },
+            // the client is built with the TLS settings
+            client: SharedClient::default(),
+            ..settings
+        };
+        let port = run_tls_server(false).await;
+        assert_eq!(get(port, &settings).await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate() {
+        let settings = HttpSettings {
+            retry_max: 0,
+            tls: TlsSettings {
+                ca_file: CA_FILE.to_string(),
+                ..Default::default()
+            },
+            ..fast_http_settings()
+        };
+        let port = run_tls_server(true).await;
+        assert!(get(port, &settings).await.is_err());
+
+        let settings = HttpSettings {
+            tls: TlsSettings {
+                ca_file: CA_FILE.to_string(),
+                cert_file: "test/tls/client.pem".to_string(),
+                key_file: "test/tls/client.key".to_string(),
+                ..Default::default()
+            },
+            client: SharedClient::default(),
+            ..settings
+        };
+        let port = run_tls_server(true).await;
+        assert_eq!(get(port, &settings).await.unwrap(), "ok");
+    }
+}
@@ -0,0 +1,27 @@
+#!/usr/bin/env bash
+
+set -o errexit
+dir_path=$(dirname "$0")
+cd "$dir_path"
+
+rm -rf tls
+mkdir -p tls
+cd tls
+
+# the internal CA of the self-managed Gitlab
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days 36500 \
+  -subj "/CN=skip-ci test CA" -keyout ca.key -out ca.pem \
+  -addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign,cRLSign"
+
+# the certificate of the server (localhost) and of the client (mTLS)
+for name in server client; do
+  openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+    -subj "/CN=skip-ci test $name" -keyout "$name.key" -out "$name.csr"
+done
+openssl x509 -req -in server.csr -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 \
+  -extfile <(printf "subjectAltName=DNS:localhost,IP:127.0.0.1\nextendedKeyUsage=serverAuth") \
+  -out server.pem
+openssl x509 -req -in client.csr -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 \
+  -extfile <(printf "extendedKeyUsage=clientAuth") \
+  -out client.pem
+rm -f ./*.csr ca.key ca.srl